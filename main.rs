@@ -52,6 +52,14 @@ fn main() {
     let mut event_pump = sdl.event_pump().unwrap();
 
     let mut world = world::World::new(win_info.get_ratio());
+    // optional gltf scenes to use as scenery and bvh files for the player to dance to
+    for arg in std::env::args().skip(1) {
+        let path = Path::new(&arg);
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("bvh") => world.load_motion(path),
+            _ => world.load_scene(path),
+        }
+    }
 
     let mut timer = timer::Timer::new();
 
@@ -63,6 +71,7 @@ fn main() {
         for event in event_pump.poll_iter() {
             input::window_input(&event, &mut win_info);
             input::mouse_input(&event, &mut world.camera);
+            input::debug_input(&event, &mut world.skeleton_debug);
         }
 
         world
@@ -79,6 +88,7 @@ fn main() {
 
         world.render();
         world.render_skeletal_animations();
+        world.render_debug(win_info.w as f32, win_info.h as f32);
 
         recorder.capture();
 
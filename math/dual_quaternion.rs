@@ -0,0 +1,203 @@
+//----------------------------------------------------------------------------------------------------
+//----------------------------------------------------------------------------------------------------
+// dual quaternions, a rotation and a translation packed into two quaternions
+// "gabor szauer - hands on c++ game animation programming packt" chapter 14 again
+// same hamilton order as Quat, a * b applies b first then a
+// there is no scaling in here, anything scaled has to go through matrices
+
+use crate::math::{mat4::*, quaternion::*, vec3::*};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DualQuat {
+    /// the rotation
+    pub real: Quat,
+    /// half the translation multiplied by the rotation
+    pub dual: Quat,
+}
+
+impl DualQuat {
+    pub const IDENTITY: Self = Self {
+        real: Quat::ZERO,
+        dual: Quat {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            s: 0.0,
+        },
+    };
+
+    pub fn new(rotation: Quat, translation: Vec3) -> Self {
+        let real = rotation.unit();
+        let t = quat(translation.x, translation.y, translation.z, 0.0);
+
+        Self {
+            real,
+            dual: (t * real) * 0.5,
+        }
+    }
+
+    /// rotation and translation of the matrix, scaling is dropped
+    pub fn from_mat(mat: &Mat4) -> Self {
+        let d = &mat.data;
+        let translation = vec3(d[0][3], d[1][3], d[2][3]);
+
+        // to_quat expects a pure rotation so the scale gets divided out of the axes first
+        let x = vec3(d[0][0], d[1][0], d[2][0]).unit();
+        let y = vec3(d[0][1], d[1][1], d[2][1]).unit();
+        let z = vec3(d[0][2], d[1][2], d[2][2]).unit();
+        let rotation = Mat4 {
+            data: [
+                [x.x, y.x, z.x, 0.0],
+                [x.y, y.y, z.y, 0.0],
+                [x.z, y.z, z.z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        };
+
+        Self::new(rotation.to_quat(), translation)
+    }
+
+    pub fn to_mat(&self) -> Mat4 {
+        let t = self.get_translation();
+        let mut mat = self.real.unit().to_mat();
+        mat.data[0][3] = t.x;
+        mat.data[1][3] = t.y;
+        mat.data[2][3] = t.z;
+
+        mat
+    }
+
+    pub fn get_translation(&self) -> Vec3 {
+        let dq = self.unit();
+        let t = (dq.dual * 2.0) * dq.real.conjugate();
+        vec3(t.x, t.y, t.z)
+    }
+
+    pub fn dot(&self, rhs: &Self) -> f32 {
+        self.real.dot(&rhs.real)
+    }
+
+    /// only the real part has to be unit length, the dual part is scaled along with it
+    pub fn unit(&self) -> Self {
+        let len = self.real.norm();
+        if len < 0.000001 {
+            return *self;
+        }
+
+        *self * (1.0 / len)
+    }
+
+    pub fn transform_point(&self, point: &Vec3) -> Vec3 {
+        self.real * *point + self.get_translation()
+    }
+
+    /// directions only get rotated
+    pub fn transform_vector(&self, vector: &Vec3) -> Vec3 {
+        self.real * *vector
+    }
+}
+
+use std::ops::*;
+
+impl Add for DualQuat {
+    type Output = DualQuat;
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            real: self.real + rhs.real,
+            dual: self.dual + rhs.dual,
+        }
+    }
+}
+
+impl Neg for DualQuat {
+    type Output = DualQuat;
+    fn neg(self) -> Self::Output {
+        Self {
+            real: -self.real,
+            dual: -self.dual,
+        }
+    }
+}
+
+impl Mul<f32> for DualQuat {
+    type Output = DualQuat;
+    fn mul(self, rhs: f32) -> Self::Output {
+        Self {
+            real: self.real * rhs,
+            dual: self.dual * rhs,
+        }
+    }
+}
+
+impl Mul<DualQuat> for DualQuat {
+    type Output = DualQuat;
+    fn mul(self, rhs: DualQuat) -> Self::Output {
+        Self {
+            real: self.real * rhs.real,
+            dual: self.real * rhs.dual + self.dual * rhs.real,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).len() < 0.0001
+    }
+
+    #[test]
+    fn known_values() {
+        let p = vec3(1.0, 0.0, 0.0);
+
+        let moved = DualQuat::new(Quat::ZERO, vec3(1.0, 2.0, 3.0));
+        assert!(close(moved.transform_point(&p), vec3(2.0, 2.0, 3.0)));
+        assert!(close(moved.transform_vector(&p), p));
+
+        // quarter turn around y takes x onto -z, then the translation is added
+        let dq = DualQuat::new(Quat::create(90.0, vec3(0.0, 1.0, 0.0)), vec3(1.0, 2.0, 3.0));
+        assert!(close(dq.transform_point(&p), vec3(1.0, 2.0, 2.0)));
+        assert!(close(dq.get_translation(), vec3(1.0, 2.0, 3.0)));
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        let rotation = Quat::create(-35.0, vec3(1.0, 1.0, 0.0));
+        let dq = DualQuat::new(rotation, vec3(-4.0, 0.5, 2.0));
+        let back = DualQuat::from_mat(&dq.to_mat());
+
+        let p = vec3(0.3, -1.0, 2.0);
+        assert!(close(back.transform_point(&p), dq.transform_point(&p)));
+        assert!(close(back.get_translation(), dq.get_translation()));
+    }
+
+    #[test]
+    fn product_applies_right_hand_side_first() {
+        let a = DualQuat::new(Quat::create(60.0, vec3(0.0, 0.0, 1.0)), vec3(1.0, 0.0, 0.0));
+        let b = DualQuat::new(
+            Quat::create(20.0, vec3(1.0, 0.0, 0.0)),
+            vec3(0.0, 3.0, -1.0),
+        );
+
+        let p = vec3(2.0, 1.0, 0.5);
+        assert!(close(
+            (a * b).transform_point(&p),
+            a.transform_point(&b.transform_point(&p))
+        ));
+    }
+
+    #[test]
+    fn blending_with_itself_is_the_same_transform() {
+        let dq = DualQuat::new(
+            Quat::create(120.0, vec3(0.0, 1.0, 1.0)),
+            vec3(5.0, 0.0, 1.0),
+        );
+        let blended = (dq * 0.25 + dq * 0.75).unit();
+        let flipped = (-dq).unit();
+
+        let p = vec3(1.0, 1.0, 1.0);
+        assert!(close(blended.transform_point(&p), dq.transform_point(&p)));
+        assert!(close(flipped.transform_point(&p), dq.transform_point(&p)));
+    }
+}
@@ -2,4 +2,5 @@ pub mod mat4;
 pub mod misc;
 pub mod vec3;
 pub mod vec2;
-pub mod quaternion;
\ No newline at end of file
+pub mod quaternion;
+pub mod dual_quaternion;
\ No newline at end of file
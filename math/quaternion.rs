@@ -95,6 +95,33 @@ impl Quat {
         (*self + (other - *self) * c).unit()
     }
 
+    /// shortest rotation that takes the direction 'from' onto 'to'
+    pub fn from_to(from: &Vec3, to: &Vec3) -> Quat {
+        let f = from.unit();
+        let t = to.unit();
+
+        if f == t {
+            return Quat::ZERO;
+        }
+        // opposite directions, rotate 180 degrees around any perpendicular axis
+        if f == -t {
+            let mut ortho = vec3(1.0, 0.0, 0.0);
+            if f.y.abs() < f.x.abs() {
+                ortho = vec3(0.0, 1.0, 0.0);
+            }
+            if f.z.abs() < f.y.abs() && f.z.abs() < f.x.abs() {
+                ortho = vec3(0.0, 0.0, 1.0);
+            }
+            let axis = cross(&f, &ortho).unit();
+            return quat(axis.x, axis.y, axis.z, 0.0);
+        }
+
+        let half = (f + t).unit();
+        let axis = cross(&f, &half);
+
+        quat(axis.x, axis.y, axis.z, dot(&f, &half))
+    }
+
     pub fn axis(&self) -> Vec3 {
         vec3(self.x, self.y, self.z)
     }
@@ -213,3 +240,51 @@ impl Mul<Quat> for Quat {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).len() < 0.0001
+    }
+
+    #[test]
+    fn from_to_takes_from_onto_to() {
+        let pairs = [
+            (vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0)),
+            (vec3(0.0, 0.0, 1.0), vec3(1.0, 1.0, 0.0)),
+            (vec3(0.3, -2.0, 0.5), vec3(-1.0, 0.2, 4.0)),
+        ];
+
+        for (from, to) in pairs {
+            let q = Quat::from_to(&from, &to);
+            assert!((q.norm() - 1.0).abs() < 0.0001);
+            assert!(close(q * from.unit(), to.unit()));
+        }
+    }
+
+    #[test]
+    fn from_to_known_values() {
+        // a quarter turn around z
+        let q = Quat::from_to(&vec3(1.0, 0.0, 0.0), &vec3(0.0, 1.0, 0.0));
+        let half = 0.5f32.sqrt();
+        assert!((q.z - half).abs() < 0.0001 && (q.s - half).abs() < 0.0001);
+
+        // same direction is no rotation at all
+        let q = Quat::from_to(&vec3(0.0, 2.0, 0.0), &vec3(0.0, 1.0, 0.0));
+        assert_eq!(q, Quat::ZERO);
+    }
+
+    #[test]
+    fn from_to_opposite_directions() {
+        for from in [
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(0.0, 0.0, -1.0),
+        ] {
+            let q = Quat::from_to(&from, &-from);
+            assert!(close(q * from, -from));
+        }
+    }
+}
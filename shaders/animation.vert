@@ -24,12 +24,79 @@ const int MAX_BONES = 300;
 const int MAX_BONE_INFLUENCE = 4;
 uniform mat4 boneMats[MAX_BONES];
 
+// 0 linear blend skinning, 1 dual quaternion skinning
+// dual quaternions share boneMats to stay inside the uniform limits, the real part goes in
+// column 0 and the dual part in column 1, quaternions stored as x y z s
+uniform int skinningMode;
+
+vec4 quatMul(vec4 a, vec4 b) {
+    return vec4(a.w * b.xyz + b.w * a.xyz + cross(a.xyz, b.xyz), a.w * b.w - dot(a.xyz, b.xyz));
+}
+
+// blends the dual quaternions and turns the result into a matrix
+mat4 dualQuatSkin() {
+    vec4 real = vec4(0.0);
+    vec4 dual = vec4(0.0);
+    vec4 first = boneIds[0] >= 0 ? boneMats[boneIds[0]][0] : vec4(0.0, 0.0, 0.0, 1.0);
+
+    for (int i = 0; i < MAX_BONE_INFLUENCE; i++) {
+        if (boneIds[i] < 0 || weights[i] == 0.0) {
+            continue;
+        }
+        vec4 r = boneMats[boneIds[i]][0];
+        vec4 d = boneMats[boneIds[i]][1];
+        // keep every influence on the same hemisphere as the first
+        float w = dot(first, r) < 0.0 ? -weights[i] : weights[i];
+        real += r * w;
+        dual += d * w;
+    }
+
+    float len = length(real);
+    if (len == 0.0) {
+        return mat4(1.0);
+    }
+    real /= len;
+    dual /= len;
+
+    vec3 t = 2.0 * quatMul(dual, vec4(-real.xyz, real.w)).xyz;
+    float x = real.x, y = real.y, z = real.z, s = real.w;
+
+    // column major
+    return mat4(
+        1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + s * z), 2.0 * (x * z - s * y), 0.0,
+        2.0 * (x * y - s * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + s * x), 0.0,
+        2.0 * (x * z + s * y), 2.0 * (y * z - s * x), 1.0 - 2.0 * (x * x + y * y), 0.0,
+        t.x, t.y, t.z, 1.0);
+}
+
+// weighted sum of the joint matrices, same as skinning::skin_matrix on the cpu
+// unused slots(-1 or no weight) are skipped and vertices without any weights stay where they are
+mat4 linearSkin() {
+    mat4 skin = mat4(0.0);
+    float total = 0.0;
+
+    for (int i = 0; i < MAX_BONE_INFLUENCE; i++) {
+        if (boneIds[i] < 0 || boneIds[i] >= MAX_BONES || weights[i] == 0.0) {
+            continue;
+        }
+        skin += boneMats[boneIds[i]] * weights[i];
+        total += weights[i];
+    }
+
+    if (total == 0.0) {
+        return mat4(1.0);
+    }
+    return skin;
+}
+
 void main() {
 
-    mat4 skin = boneMats[boneIds[0]] * weights[0];
-    skin += boneMats[boneIds[1]] * weights[1];
-    skin += boneMats[boneIds[2]] * weights[2];
-    skin += boneMats[boneIds[3]] * weights[3];
+    mat4 skin;
+    if (skinningMode == 1) {
+        skin = dualQuatSkin();
+    } else {
+        skin = linearSkin();
+    }
 
     mat4 final_mat = transform * skin;
     gl_Position = projection * view * final_mat * vec4(pos, 1.0);
@@ -0,0 +1,15 @@
+#version 460
+
+layout(location = 0) in vec3 pos;
+layout(location = 1) in vec3 col;
+
+uniform mat4 view;
+uniform mat4 projection;
+
+out vec3 fragCol;
+
+// lines are already in world space
+void main() {
+    fragCol = col;
+    gl_Position = projection * view * vec4(pos, 1.0);
+}
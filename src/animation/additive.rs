@@ -0,0 +1,136 @@
+use crate::src::animation::animator::Animator;
+use crate::src::animation::blending;
+use crate::src::animation::clip::Clip;
+use crate::src::animation::pose::Pose;
+
+use std::rc::Rc;
+
+/// an additive clip layered on top of whatever the base layer produced
+/// only the difference between the sampled clip and its reference pose is added
+/// breathing, leaning, hit reactions etc...
+#[derive(Clone)]
+pub struct AdditiveLayer {
+    /// playback state, its weight scales the added difference
+    pub animator: Animator,
+    /// only add to this joint and its children, -1 for the whole pose
+    pub blend_root: i32,
+    base_pose: Pose,
+    pose: Pose,
+}
+
+impl AdditiveLayer {
+    /// uses the first frame of the clip as the reference pose
+    pub fn new(clip: Rc<Clip>, rest_pose: &Pose) -> Self {
+        let base_pose = blending::make_additive_pose(rest_pose, &clip);
+
+        Self::with_reference(clip, rest_pose, base_pose)
+    }
+
+    /// same as 'new' but with a user supplied reference pose
+    pub fn with_reference(clip: Rc<Clip>, rest_pose: &Pose, base_pose: Pose) -> Self {
+        Self {
+            animator: Animator::new(clip),
+            blend_root: -1,
+            base_pose,
+            pose: rest_pose.clone(),
+        }
+    }
+
+    /// advance the layer and add it on top of 'pose'
+    pub fn apply(&mut self, pose: &mut Pose, dt: f32) {
+        if self.animator.weight <= 0.0 {
+            return;
+        }
+
+        self.animator.advance(dt);
+        self.animator.sample(&mut self.pose);
+
+        let input = pose.clone();
+        blending::add(
+            pose,
+            &input,
+            &self.pose,
+            &self.base_pose,
+            self.animator.weight,
+            self.blend_root,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::quaternion::Quat;
+    use crate::math::vec3::*;
+    use crate::src::animation::curves::Interpolation;
+    use crate::src::animation::frame::{QuaternionFrame, VectorFrame};
+    use crate::src::animation::track_transform::TransformTrack;
+
+    /// joints 1 and 2 slide one unit along x over a second, joint 1 also turns 90 degrees
+    fn lean() -> Rc<Clip> {
+        let mut clip = Clip::new();
+        for id in [1, 2] {
+            let mut track = TransformTrack::new();
+            track.id = id;
+            track.position.interpolation = Interpolation::Linear;
+            for (time, x) in [(0.0, 0.0), (1.0, 1.0)] {
+                let mut frame = VectorFrame::new();
+                frame.time = time;
+                frame.m_value = [x, 0.0, 0.0];
+                track.position.frames.push(frame);
+            }
+            clip.tracks.push(track);
+        }
+
+        let track = &mut clip.tracks[0];
+        track.rotation.interpolation = Interpolation::Linear;
+        for (time, degrees) in [(0.0, 0.0), (1.0, 90.0)] {
+            let q = Quat::create(degrees, vec3(0.0, 1.0, 0.0));
+            let mut frame = QuaternionFrame::new();
+            frame.time = time;
+            frame.m_value = q.to_array();
+            track.rotation.frames.push(frame);
+        }
+
+        clip.re_calculate_duration();
+        Rc::new(clip)
+    }
+
+    /// joint 1 hangs below joint 0, joint 2 is a separate root
+    fn rest_pose() -> Pose {
+        let mut pose = Pose::new();
+        pose.resize(3);
+        pose.parents[1] = 0;
+        pose
+    }
+
+    #[test]
+    fn weight_scales_the_difference_to_the_first_frame() {
+        let rest = rest_pose();
+        let mut layer = AdditiveLayer::new(lean(), &rest);
+        layer.animator.set_weight(0.5);
+
+        let mut pose = rest.clone();
+        pose.joints[1].translation = vec3(5.0, 0.0, 0.0);
+        layer.apply(&mut pose, 0.5);
+
+        // half of the way the clip moved at half the weight
+        assert!((pose.joints[1].translation - vec3(5.25, 0.0, 0.0)).len() < 0.0001);
+        assert!((pose.joints[2].translation - vec3(0.25, 0.0, 0.0)).len() < 0.0001);
+        let expected = Quat::create(22.5, vec3(0.0, 1.0, 0.0));
+        assert!((pose.joints[1].orientation.dot(&expected)).abs() > 0.9999);
+    }
+
+    #[test]
+    fn blend_root_keeps_other_joints() {
+        let rest = rest_pose();
+        let mut layer = AdditiveLayer::new(lean(), &rest);
+        layer.blend_root = 0;
+
+        let mut pose = rest.clone();
+        layer.apply(&mut pose, 0.5);
+
+        assert!((pose.joints[1].translation - vec3(0.5, 0.0, 0.0)).len() < 0.0001);
+        assert!(pose.joints[2].translation.len() < 0.0001);
+    }
+}
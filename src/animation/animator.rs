@@ -0,0 +1,427 @@
+use crate::src::animation::bone_mask::BoneMask;
+use crate::src::animation::clip::{AnimationEvent, Clip};
+use crate::src::animation::pose::Pose;
+use crate::src::animation::root_motion::RootMotion;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// playback state for a single clip instance
+// clips themselves never change while playing, so any number of animators(and objects) can share one
+// through an Rc instead of every character carrying its own copy of every keyframe
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PlayMode {
+    /// wrap back to the start after the end
+    Loop,
+    /// play once and hold the last frame
+    Once,
+    /// bounce back and forth between start and end
+    PingPong,
+    /// loop backwards from the end to the start
+    Reverse,
+}
+
+/// where a playhead ends up after step_time
+#[derive(Clone, Copy, Debug)]
+pub struct TimeStep {
+    pub time: f32,
+    /// ping pong direction after the step
+    pub direction: f32,
+    /// how far the playhead moved before wrapping, negative going backwards
+    /// ping pong gives the net distance, the turns are counted in 'bounces'
+    pub travelled: f32,
+    /// went past either end of a looping playhead
+    pub wrapped: bool,
+    /// how often a ping pong playhead turned around, alternating between the ends
+    pub bounces: u32,
+    /// a 'Once' playhead reached its end
+    pub finished: bool,
+}
+
+/// moves 'time' by 'step' seconds between 'start' and 'end' the way 'mode' plays
+/// direction is the ping pong direction(1.0 or -1.0), reverse turns the step around by itself
+/// the one place play modes are implemented, animators and tweens both go through here
+pub fn step_time(
+    mode: PlayMode,
+    time: f32,
+    direction: f32,
+    step: f32,
+    start: f32,
+    end: f32,
+) -> TimeStep {
+    let duration = end - start;
+    let mut result = TimeStep {
+        time,
+        direction,
+        travelled: 0.0,
+        wrapped: false,
+        bounces: 0,
+        finished: false,
+    };
+    if duration <= 0.0 {
+        return result;
+    }
+
+    match mode {
+        PlayMode::Loop | PlayMode::Reverse => {
+            let step = if mode == PlayMode::Reverse {
+                -step
+            } else {
+                step
+            };
+            let unwrapped = time + step;
+            result.travelled = step;
+            result.wrapped = unwrapped < start || unwrapped >= end;
+            result.time = start + (unwrapped - start).rem_euclid(duration);
+        }
+        PlayMode::Once => {
+            result.time = (time + step).clamp(start, end);
+            result.travelled = result.time - time;
+            result.finished =
+                (step >= 0.0 && result.time >= end) || (step < 0.0 && result.time <= start);
+        }
+        PlayMode::PingPong => {
+            let mut unwrapped = time + step * direction;
+            // bounce off the ends, a long update can bounce more than once
+            while unwrapped > end || unwrapped < start {
+                if unwrapped > end {
+                    unwrapped = 2.0 * end - unwrapped;
+                } else {
+                    unwrapped = 2.0 * start - unwrapped;
+                }
+                result.direction = -result.direction;
+                result.bounces += 1;
+            }
+            result.time = unwrapped;
+            result.travelled = result.time - time;
+        }
+    }
+
+    result
+}
+
+#[derive(Clone)]
+pub struct Animator {
+    clip: Rc<Clip>,
+    time: f32,
+    /// playback rate, 1.0 is normal speed
+    pub speed: f32,
+    pub mode: PlayMode,
+    /// how much this instance contributes when it is layered or blended
+    pub weight: f32,
+    /// ping pong direction, 1.0 forwards -1.0 backwards
+    direction: f32,
+    /// set once a 'Once' clip reaches its end
+    finished: bool,
+    /// true if the last advance wrapped around the clip
+    wrapped: bool,
+    /// how far the last advance moved the playhead before wrapping, negative going backwards
+    travelled: f32,
+    /// how often the last advance turned a ping pong playhead around
+    bounces: u32,
+    /// nothing has been played since the last restart
+    fresh: bool,
+    /// the last advance started from a restart, so events right at its start time count too
+    from_restart: bool,
+}
+
+impl Animator {
+    /// loops if the clip is marked as looping, otherwise plays once
+    pub fn new(clip: Rc<Clip>) -> Self {
+        let mode = if clip.is_looping() {
+            PlayMode::Loop
+        } else {
+            PlayMode::Once
+        };
+
+        let mut animator = Self {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            mode,
+            weight: 1.0,
+            direction: 1.0,
+            finished: false,
+            wrapped: false,
+            travelled: 0.0,
+            bounces: 0,
+            fresh: true,
+            from_restart: false,
+        };
+        animator.restart();
+
+        animator
+    }
+
+    pub fn set_speed(&mut self, speed: f32) -> &mut Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn set_mode(&mut self, mode: PlayMode) -> &mut Self {
+        self.mode = mode;
+        self.restart();
+        self
+    }
+
+    pub fn set_weight(&mut self, weight: f32) -> &mut Self {
+        self.weight = weight;
+        self
+    }
+
+    /// jump back to the first frame for the current play mode
+    pub fn restart(&mut self) {
+        self.time = match self.mode {
+            PlayMode::Reverse => self.clip.get_end_time(),
+            _ => self.clip.get_start_time(),
+        };
+        self.direction = 1.0;
+        self.finished = false;
+        self.wrapped = false;
+        self.travelled = 0.0;
+        self.bounces = 0;
+        self.fresh = true;
+        self.from_restart = false;
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.clamp(self.clip.get_start_time(), self.clip.get_end_time());
+    }
+
+    pub fn get_time(&self) -> f32 {
+        self.time
+    }
+
+    /// 0.0 at the start of the clip, 1.0 at the end
+    pub fn get_normalized_time(&self) -> f32 {
+        let duration = self.clip.get_duration();
+        if duration <= 0.0 {
+            return 0.0;
+        }
+
+        (self.time - self.clip.get_start_time()) / duration
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn get_clip(&self) -> &Rc<Clip> {
+        &self.clip
+    }
+
+    /// move the playhead, returns the time before the update
+    pub fn advance(&mut self, dt: f32) -> f32 {
+        let previous = self.time;
+        self.wrapped = false;
+        self.travelled = 0.0;
+        self.bounces = 0;
+        self.from_restart = self.fresh;
+        self.fresh = false;
+        if self.finished {
+            return previous;
+        }
+
+        let step = step_time(
+            self.mode,
+            self.time,
+            self.direction,
+            dt * self.speed,
+            self.clip.get_start_time(),
+            self.clip.get_end_time(),
+        );
+        self.time = step.time;
+        self.direction = step.direction;
+        self.travelled = step.travelled;
+        self.wrapped = step.wrapped;
+        self.bounces = step.bounces;
+        self.finished = step.finished;
+
+        previous
+    }
+
+    /// wraps are only possible when looping, ping pong and once always stay in range
+    pub fn is_looping(&self) -> bool {
+        matches!(self.mode, PlayMode::Loop | PlayMode::Reverse)
+    }
+
+    /// pose at the current time
+    pub fn sample(&self, pose: &mut Pose) {
+        self.clip.sample_with(pose, self.time, self.is_looping());
+    }
+
+    /// pose at the current time but only for the joints in the mask, see Clip::sample_masked
+    pub fn sample_masked(&self, pose: &mut Pose, mask: &BoneMask) {
+        self.clip
+            .sample_masked_with(pose, self.time, self.is_looping(), mask);
+    }
+
+    pub fn sample_weights(&self, out: &mut HashMap<u32, Vec<f32>>) {
+        self.clip.sample_weights(out, self.time);
+    }
+
+    /// call after 'sample' with the time 'advance' returned
+    pub fn extract_root_motion(&self, pose: &mut Pose, previous: f32) -> RootMotion {
+        let mut wraps = 0;
+        let duration = self.clip.get_duration();
+        if self.wrapped && duration > 0.0 {
+            // a long update can go around the clip more than once
+            let unwrapped = previous - self.clip.get_start_time() + self.travelled;
+            wraps = unwrapped.div_euclid(duration) as i32;
+        }
+
+        self.clip
+            .extract_root_motion_wrapped(pose, previous, self.time, wraps)
+    }
+
+    /// events crossed since 'previous' in the order they were passed, works in both directions
+    /// an update spanning several loops reports the events once per loop
+    /// ping pong reports every stretch between the turns, so events passed on the way there and back both count
+    pub fn crossed_events(&self, previous: f32) -> Vec<&AnimationEvent> {
+        if self.bounces == 0 {
+            return self.clip.crossed_events(
+                previous,
+                previous + self.travelled,
+                self.is_looping(),
+                self.from_restart,
+            );
+        }
+
+        // every turn flipped the direction, so the direction the update started with can be worked back
+        let mut direction = if self.bounces.is_multiple_of(2) {
+            self.direction
+        } else {
+            -self.direction
+        };
+        let mut events = Vec::new();
+        let mut from = previous;
+        let mut include_from = self.from_restart;
+        for _ in 0..self.bounces {
+            let to = if direction > 0.0 {
+                self.clip.get_end_time()
+            } else {
+                self.clip.get_start_time()
+            };
+            events.extend(self.clip.crossed_events(from, to, false, include_from));
+
+            // an event right on the end was reported on the way in
+            from = to;
+            include_from = false;
+            direction = -direction;
+        }
+        events.extend(
+            self.clip
+                .crossed_events(from, self.time, false, include_from),
+        );
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::src::animation::frame::VectorFrame;
+    use crate::src::animation::track_transform::TransformTrack;
+
+    /// two seconds long with an event right at the start and one in the middle
+    fn clip() -> Rc<Clip> {
+        let mut track = TransformTrack::new();
+        for time in [0.0, 2.0] {
+            let mut frame = VectorFrame::new();
+            frame.time = time;
+            track.position.frames.push(frame);
+        }
+
+        let mut clip = Clip::new();
+        clip.tracks.push(track);
+        clip.add_event("start", 0.0);
+        clip.add_event("middle", 1.0);
+        clip.re_calculate_duration();
+        Rc::new(clip)
+    }
+
+    fn advance(animator: &mut Animator, dt: f32) -> Vec<String> {
+        let previous = animator.advance(dt);
+        animator
+            .crossed_events(previous)
+            .iter()
+            .map(|event| event.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn start_event_is_reported_after_a_restart() {
+        let mut animator = Animator::new(clip());
+        assert_eq!(advance(&mut animator, 0.5), ["start"]);
+        assert!(advance(&mut animator, 0.25).is_empty());
+
+        animator.set_mode(PlayMode::Once);
+        assert_eq!(advance(&mut animator, 0.5), ["start"]);
+        assert_eq!(advance(&mut animator, 0.5), ["middle"]);
+        assert!(advance(&mut animator, 5.0).is_empty());
+    }
+
+    #[test]
+    fn long_updates_report_every_loop() {
+        let mut animator = Animator::new(clip());
+        advance(&mut animator, 0.5);
+
+        // 0.5 -> 4.5 passes the end twice
+        assert_eq!(
+            advance(&mut animator, 4.0),
+            ["middle", "start", "middle", "start"]
+        );
+        assert!((animator.get_time() - 0.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn reverse_reports_events_backwards() {
+        let mut animator = Animator::new(clip());
+        animator.set_mode(PlayMode::Reverse);
+
+        assert_eq!(advance(&mut animator, 1.5), ["middle"]);
+        assert_eq!(advance(&mut animator, 0.5), ["start"]);
+        assert!(advance(&mut animator, 0.5).is_empty());
+    }
+
+    #[test]
+    fn ping_pong_reports_events_both_ways() {
+        let mut clip = (*clip()).clone();
+        clip.add_event("late", 1.9);
+        let mut animator = Animator::new(Rc::new(clip));
+        animator.set_mode(PlayMode::PingPong);
+        advance(&mut animator, 1.5);
+
+        // 1.5 -> 2.0 -> 1.5 passes 'late' on the way there and on the way back
+        assert_eq!(advance(&mut animator, 1.0), ["late", "late"]);
+        assert!((animator.get_time() - 1.5).abs() < 0.0001);
+
+        // back down through the middle and the start, then up to 0.5 again
+        assert_eq!(advance(&mut animator, 2.0), ["middle", "start"]);
+        assert_eq!(advance(&mut animator, 0.75), ["middle"]);
+    }
+
+    #[test]
+    fn step_time_modes() {
+        let step = step_time(PlayMode::Loop, 1.5, 1.0, 1.0, 0.0, 2.0);
+        assert!(step.wrapped && (step.time - 0.5).abs() < 0.0001);
+
+        let step = step_time(PlayMode::Once, 1.5, 1.0, 1.0, 0.0, 2.0);
+        assert!(step.finished && step.time == 2.0);
+
+        let step = step_time(PlayMode::PingPong, 1.5, 1.0, 1.0, 0.0, 2.0);
+        assert!(step.direction == -1.0 && (step.time - 1.5).abs() < 0.0001);
+        assert_eq!(step.bounces, 1);
+
+        let step = step_time(PlayMode::PingPong, 1.5, 1.0, 3.0, 0.0, 2.0);
+        assert!(step.direction == 1.0 && (step.time - 0.5).abs() < 0.0001);
+        assert_eq!(step.bounces, 2);
+
+        let step = step_time(PlayMode::Reverse, 0.25, 1.0, 0.5, 0.0, 2.0);
+        assert!(step.wrapped && (step.time - 1.75).abs() < 0.0001);
+    }
+}
@@ -1,4 +1,7 @@
 use crate::math::{quaternion::*, vec3::*};
+use crate::src::animation::curves::Interpolation;
+use crate::src::animation::frame::{QuaternionFrame, VectorFrame};
+use crate::src::animation::track::{QuatTrack, VectorTrack};
 use crate::src::transform::Transform;
 
 // _______________________________________________________________________________________________________
@@ -26,3 +29,53 @@ pub fn rotate_around(
 
     *pos = result * radius + center;
 }
+
+// keyframed versions of the functions above, for building clips in code
+// duration: seconds of animation to bake, samples_per_second: how many keyframes per second
+
+/// track version of 'spin'
+pub fn spin_track(angle: f32, axis: Vec3, duration: f32, samples_per_second: f32) -> QuatTrack {
+    let mut track = QuatTrack::new();
+    track.interpolation = Interpolation::Linear;
+
+    let samples = (duration * samples_per_second).ceil().max(1.0) as usize;
+    for i in 0..=samples {
+        let time = duration * i as f32 / samples as f32;
+        let mut transform = Transform::DEFAULT;
+        spin(time, angle, axis, &mut transform);
+
+        let mut frame = QuaternionFrame::new();
+        frame.time = time;
+        frame.m_value = transform.orientation.to_array();
+        track.frames.push(frame);
+    }
+
+    track
+}
+
+/// track version of 'rotate_around'
+pub fn rotate_around_track(
+    center: Vec3,
+    radius: f32,
+    angle: f32,
+    axis: Vec3,
+    duration: f32,
+    samples_per_second: f32,
+) -> VectorTrack {
+    let mut track = VectorTrack::new();
+    track.interpolation = Interpolation::Linear;
+
+    let samples = (duration * samples_per_second).ceil().max(1.0) as usize;
+    for i in 0..=samples {
+        let time = duration * i as f32 / samples as f32;
+        let mut pos = Vec3::ZERO;
+        rotate_around(center, radius, angle, axis, time, &mut pos);
+
+        let mut frame = VectorFrame::new();
+        frame.time = time;
+        frame.m_value = pos.to_array();
+        track.frames.push(frame);
+    }
+
+    track
+}
@@ -0,0 +1,479 @@
+use crate::math::vec2::*;
+use crate::src::animation::blending;
+use crate::src::animation::clip::Clip;
+use crate::src::animation::pose::Pose;
+
+use std::rc::Rc;
+
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// blend spaces
+// clips get placed at points along one(speed) or two(speed, direction) parameters
+// and the clips closest to the current parameter are blended together
+// every clip plays at the same normalized time so the feet of a walk and a run stay in step
+// only the pose is blended, root motion, events and morph weights of the clips are not played back
+
+/// shared playback for both blend spaces
+/// keeps a normalized phase that moves at the weighted average speed of the blended clips
+#[derive(Clone)]
+struct SyncedPlayback {
+    /// 0.0 - 1.0 through every clip
+    phase: f32,
+    pose: Pose,
+}
+
+impl SyncedPlayback {
+    fn new() -> Self {
+        Self {
+            phase: 0.0,
+            pose: Pose::new(),
+        }
+    }
+
+    /// weights: (clip, weight) pairs, weights are expected to add up to 1.0
+    fn update(&mut self, weights: &[(&Clip, f32)], rest_pose: &Pose, dt: f32) {
+        self.pose = rest_pose.clone();
+        if weights.is_empty() {
+            return;
+        }
+
+        let duration: f32 = weights
+            .iter()
+            .map(|(clip, weight)| clip.get_duration() * weight)
+            .sum();
+        if duration > 0.0 {
+            self.phase = (self.phase + dt / duration).fract();
+        }
+
+        // running weighted average, each pose gets lerped in by its share of the total so far
+        let mut total = 0.0;
+        let mut sampled = rest_pose.clone();
+        for (clip, weight) in weights.iter() {
+            if *weight <= 0.0 {
+                continue;
+            }
+            total += weight;
+
+            sampled.clone_from(rest_pose);
+            let time = clip.get_start_time() + clip.get_duration() * self.phase;
+            clip.sample(&mut sampled, time);
+
+            let current = self.pose.clone();
+            blending::blend(&mut self.pose, &current, &sampled, weight / total, -1);
+        }
+    }
+}
+
+/// clips placed along a single parameter, the two closest ones get blended linearly
+#[derive(Clone)]
+pub struct BlendSpace1D {
+    /// (position, clip) sorted by position
+    samples: Vec<(f32, Rc<Clip>)>,
+    pub parameter: f32,
+    playback: SyncedPlayback,
+}
+
+impl BlendSpace1D {
+    pub fn new() -> Self {
+        Self {
+            samples: Vec::new(),
+            parameter: 0.0,
+            playback: SyncedPlayback::new(),
+        }
+    }
+
+    pub fn add_sample(&mut self, position: f32, clip: Rc<Clip>) -> &mut Self {
+        let i = self.samples.partition_point(|(p, _)| *p < position);
+        self.samples.insert(i, (position, clip));
+        self
+    }
+
+    pub fn set_parameter(&mut self, parameter: f32) {
+        self.parameter = parameter;
+    }
+
+    /// weight of every sample for the current parameter, parallel to the order the samples are sorted in
+    pub fn get_weights(&self) -> Vec<f32> {
+        let len = self.samples.len();
+        let mut weights = vec![0.0; len];
+        if len == 0 {
+            return weights;
+        }
+
+        // past either end the closest clip plays on its own
+        let x = self.parameter;
+        if x <= self.samples[0].0 {
+            weights[0] = 1.0;
+            return weights;
+        }
+        if x >= self.samples[len - 1].0 {
+            weights[len - 1] = 1.0;
+            return weights;
+        }
+
+        for i in 0..len - 1 {
+            let (a, b) = (self.samples[i].0, self.samples[i + 1].0);
+            if x >= a && x <= b {
+                let t = if b - a > 0.0 { (x - a) / (b - a) } else { 0.0 };
+                weights[i] = 1.0 - t;
+                weights[i + 1] = t;
+                break;
+            }
+        }
+
+        weights
+    }
+
+    pub fn update(&mut self, rest_pose: &Pose, dt: f32) {
+        let weights = self.get_weights();
+        let pairs: Vec<(&Clip, f32)> = self
+            .samples
+            .iter()
+            .zip(weights)
+            .map(|((_, clip), weight)| (clip.as_ref(), weight))
+            .collect();
+
+        self.playback.update(&pairs, rest_pose, dt);
+    }
+
+    pub fn get_pose(&self) -> &Pose {
+        &self.playback.pose
+    }
+}
+
+/// clips placed on a plane, the triangle around the parameter is blended with barycentric weights
+#[derive(Clone)]
+pub struct BlendSpace2D {
+    samples: Vec<(Vec2, Rc<Clip>)>,
+    /// delaunay triangulation of the sample positions, rebuilt whenever a sample is added
+    triangles: Vec<[usize; 3]>,
+    pub parameter: Vec2,
+    playback: SyncedPlayback,
+}
+
+impl BlendSpace2D {
+    pub fn new() -> Self {
+        Self {
+            samples: Vec::new(),
+            triangles: Vec::new(),
+            parameter: Vec2::ZERO,
+            playback: SyncedPlayback::new(),
+        }
+    }
+
+    pub fn add_sample(&mut self, position: Vec2, clip: Rc<Clip>) -> &mut Self {
+        self.samples.push((position, clip));
+        let points: Vec<Vec2> = self.samples.iter().map(|(point, _)| *point).collect();
+        self.triangles = triangulate(&points);
+        self
+    }
+
+    pub fn set_parameter(&mut self, parameter: Vec2) {
+        self.parameter = parameter;
+    }
+
+    /// weight of every sample for the current parameter, parallel to the order they were added in
+    pub fn get_weights(&self) -> Vec<f32> {
+        let len = self.samples.len();
+        let mut weights = vec![0.0; len];
+        if len == 0 {
+            return weights;
+        }
+        if len == 1 {
+            weights[0] = 1.0;
+            return weights;
+        }
+
+        let p = self.parameter;
+        let points: Vec<Vec2> = self.samples.iter().map(|(point, _)| *point).collect();
+
+        // the triangles never overlap, so the first one containing the parameter is the only one
+        let inside = self.triangles.iter().find_map(|&[a, b, c]| {
+            barycentric(p, points[a], points[b], points[c])
+                .filter(|bary| bary.iter().all(|&w| w >= -0.0001))
+                .map(|bary| ([a, b, c], bary))
+        });
+
+        if let Some((ids, bary)) = inside {
+            for i in 0..3 {
+                weights[ids[i]] = bary[i].max(0.0);
+            }
+            let total: f32 = weights.iter().sum();
+            weights.iter_mut().for_each(|w| *w /= total);
+
+            return weights;
+        }
+
+        // outside of the triangulation, blend along the closest edge instead
+        // the closest edge is always on the outline, so checking every edge is fine
+        // without any triangles(samples on a line) neighbouring samples along the line make the edges
+        let mut edges: Vec<(usize, usize)> = self
+            .triangles
+            .iter()
+            .flat_map(|&[a, b, c]| [(a, b), (b, c), (c, a)])
+            .collect();
+        if edges.is_empty() {
+            let mut order: Vec<usize> = (0..len).collect();
+            order.sort_by(|&a, &b| {
+                let (a, b) = (points[a], points[b]);
+                a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y))
+            });
+            edges = order.windows(2).map(|pair| (pair[0], pair[1])).collect();
+        }
+
+        let mut closest = (f32::MAX, 0, 0, 0.0);
+        for (a, b) in edges {
+            let (t, distance) = closest_on_segment(p, points[a], points[b]);
+            if distance < closest.0 {
+                closest = (distance, a, b, t);
+            }
+        }
+
+        let (_, a, b, t) = closest;
+        weights[a] += 1.0 - t;
+        weights[b] += t;
+
+        weights
+    }
+
+    pub fn update(&mut self, rest_pose: &Pose, dt: f32) {
+        let weights = self.get_weights();
+        let pairs: Vec<(&Clip, f32)> = self
+            .samples
+            .iter()
+            .zip(weights)
+            .map(|((_, clip), weight)| (clip.as_ref(), weight))
+            .collect();
+
+        self.playback.update(&pairs, rest_pose, dt);
+    }
+
+    pub fn get_pose(&self) -> &Pose {
+        &self.playback.pose
+    }
+}
+
+/// either kind of blend space, so objects can hold one without caring which
+#[derive(Clone)]
+pub enum BlendSpace {
+    OneD(BlendSpace1D),
+    TwoD(BlendSpace2D),
+}
+
+impl BlendSpace {
+    pub fn update(&mut self, rest_pose: &Pose, dt: f32) {
+        match self {
+            BlendSpace::OneD(space) => space.update(rest_pose, dt),
+            BlendSpace::TwoD(space) => space.update(rest_pose, dt),
+        }
+    }
+
+    pub fn get_pose(&self) -> &Pose {
+        match self {
+            BlendSpace::OneD(space) => space.get_pose(),
+            BlendSpace::TwoD(space) => space.get_pose(),
+        }
+    }
+}
+
+/// delaunay triangulation(bowyer watson) of the points, triangles are indices into 'points'
+/// it keeps the triangles as close to equilateral as it can so no triangle reaches over a sample
+/// points that all sit on a line don't make any triangles
+fn triangulate(points: &[Vec2]) -> Vec<[usize; 3]> {
+    let len = points.len();
+    if len < 3 {
+        return Vec::new();
+    }
+
+    // a triangle big enough to hold every point starts things off, its corners go after the points
+    let mut min = points[0];
+    let mut max = points[0];
+    points.iter().for_each(|p| {
+        min = vec2(min.x.min(p.x), min.y.min(p.y));
+        max = vec2(max.x.max(p.x), max.y.max(p.y));
+    });
+    let size = (max.x - min.x).max(max.y - min.y).max(1.0) * 20.0;
+    let center = vec2((min.x + max.x) * 0.5, (min.y + max.y) * 0.5);
+
+    let mut all = points.to_vec();
+    all.push(vec2(center.x - size, center.y - size));
+    all.push(vec2(center.x + size, center.y - size));
+    all.push(vec2(center.x, center.y + size));
+
+    let mut triangles = vec![[len, len + 1, len + 2]];
+    for i in 0..len {
+        // every triangle whose circumcircle holds the new point gets removed
+        // the hole left behind is filled with triangles fanning out from the point
+        let (bad, good): (Vec<[usize; 3]>, Vec<[usize; 3]>) = triangles
+            .iter()
+            .partition(|&&[a, b, c]| in_circumcircle(all[i], all[a], all[b], all[c]));
+
+        let mut edges = Vec::new();
+        for &[a, b, c] in bad.iter() {
+            for (e0, e1) in [(a, b), (b, c), (c, a)] {
+                let shared = bad
+                    .iter()
+                    .filter(|other| other.contains(&e0) && other.contains(&e1))
+                    .count()
+                    > 1;
+                if !shared {
+                    edges.push((e0, e1));
+                }
+            }
+        }
+
+        triangles = good;
+        triangles.extend(edges.iter().map(|&(a, b)| [a, b, i]));
+    }
+
+    // anything still touching the starting triangle wasn't part of the real points
+    triangles.retain(|&[a, b, c]| {
+        a < len && b < len && c < len && triangle_area(all[a], all[b], all[c]) > 0.00001
+    });
+
+    triangles
+}
+
+/// works for either winding of a, b, c
+fn in_circumcircle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let (ax, ay) = (a.x - p.x, a.y - p.y);
+    let (bx, by) = (b.x - p.x, b.y - p.y);
+    let (cx, cy) = (c.x - p.x, c.y - p.y);
+
+    let det = (ax * ax + ay * ay) * (bx * cy - cx * by) - (bx * bx + by * by) * (ax * cy - cx * ay)
+        + (cx * cx + cy * cy) * (ax * by - bx * ay);
+    let winding = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+
+    if winding > 0.0 {
+        det > 0.0
+    } else {
+        det < 0.0
+    }
+}
+
+fn triangle_area(a: Vec2, b: Vec2, c: Vec2) -> f32 {
+    ((b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y)).abs() * 0.5
+}
+
+fn barycentric(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> Option<[f32; 3]> {
+    let det = (b.y - c.y) * (a.x - c.x) + (c.x - b.x) * (a.y - c.y);
+    if det.abs() < 0.00001 {
+        return None;
+    }
+
+    let u = ((b.y - c.y) * (p.x - c.x) + (c.x - b.x) * (p.y - c.y)) / det;
+    let v = ((c.y - a.y) * (p.x - c.x) + (a.x - c.x) * (p.y - c.y)) / det;
+
+    Some([u, v, 1.0 - u - v])
+}
+
+/// returns (t along the segment, distance to the closest point)
+fn closest_on_segment(p: Vec2, a: Vec2, b: Vec2) -> (f32, f32) {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let len_sq = dx * dx + dy * dy;
+    let t = if len_sq > 0.0 {
+        (((p.x - a.x) * dx + (p.y - a.y) * dy) / len_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    let (x, y) = (a.x + dx * t, a.y + dy * t);
+    let distance = ((p.x - x).powi(2) + (p.y - y).powi(2)).sqrt();
+
+    (t, distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.0001
+    }
+
+    fn space(points: &[(f32, f32)]) -> BlendSpace2D {
+        let mut space = BlendSpace2D::new();
+        points.iter().for_each(|&(x, y)| {
+            space.add_sample(vec2(x, y), Rc::new(Clip::new()));
+        });
+        space
+    }
+
+    #[test]
+    fn one_d_blends_the_two_closest_samples() {
+        let mut space = BlendSpace1D::new();
+        space
+            .add_sample(2.0, Rc::new(Clip::new()))
+            .add_sample(0.0, Rc::new(Clip::new()))
+            .add_sample(1.0, Rc::new(Clip::new()));
+
+        space.set_parameter(1.25);
+        let weights = space.get_weights();
+        assert!(close(weights[0], 0.0));
+        assert!(close(weights[1], 0.75));
+        assert!(close(weights[2], 0.25));
+
+        space.set_parameter(5.0);
+        assert_eq!(space.get_weights(), vec![0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn square_with_a_center_splits_into_four_triangles() {
+        let space = space(&[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0), (0.5, 0.5)]);
+        assert_eq!(space.triangles.len(), 4);
+        assert!(space.triangles.iter().all(|t| t.contains(&4)));
+    }
+
+    #[test]
+    fn weights_come_from_the_triangle_around_the_parameter() {
+        let mut space = space(&[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0), (0.5, 0.5)]);
+
+        space.set_parameter(vec2(0.5, 0.5));
+        let weights = space.get_weights();
+        assert!(close(weights[4], 1.0));
+
+        // left triangle, half way between the left edge and the center
+        space.set_parameter(vec2(0.25, 0.5));
+        let weights = space.get_weights();
+        assert!(close(weights[0], 0.25));
+        assert!(close(weights[2], 0.25));
+        assert!(close(weights[4], 0.5));
+        assert!(close(weights[1] + weights[3], 0.0));
+    }
+
+    #[test]
+    fn weights_follow_a_single_triangulation() {
+        // the quad gets split along 0 - 2, the parameter sits in 0, 2, 3
+        // it is also inside the smaller 1, 2, 3 which overlaps it, that one must not be used
+        let mut space = space(&[(0.0, 0.0), (4.0, 0.0), (4.0, 1.0), (0.0, 3.0)]);
+        assert_eq!(space.triangles.len(), 2);
+
+        space.set_parameter(vec2(3.5, 1.0));
+        let weights = space.get_weights();
+        assert!(close(weights[1], 0.0));
+        assert!(close(weights.iter().sum(), 1.0));
+    }
+
+    #[test]
+    fn outside_blends_along_the_closest_edge() {
+        let mut space = space(&[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]);
+        space.set_parameter(vec2(0.25, -3.0));
+
+        let weights = space.get_weights();
+        assert!(close(weights[0], 0.75));
+        assert!(close(weights[1], 0.25));
+        assert!(close(weights[2], 0.0));
+    }
+
+    #[test]
+    fn samples_on_a_line_blend_like_one_d() {
+        let mut space = space(&[(0.0, 0.0), (2.0, 0.0), (1.0, 0.0)]);
+        assert!(space.triangles.is_empty());
+
+        space.set_parameter(vec2(1.5, 1.0));
+        let weights = space.get_weights();
+        assert!(close(weights[0], 0.0));
+        assert!(close(weights[1], 0.5));
+        assert!(close(weights[2], 0.5));
+    }
+}
@@ -0,0 +1,107 @@
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// blending poses together
+// yet again lots of help from "gabor szauer - hands on c++ game animation programming packt"
+
+use crate::math::quaternion::Quat;
+use crate::src::animation::bone_mask::BoneMask;
+use crate::src::animation::clip::Clip;
+use crate::src::animation::pose::Pose;
+use crate::src::transform::Transform;
+
+/// checks if the joint at 'search' is 'parent' itself or one of its children(grand children included)
+pub fn is_in_hierarchy(pose: &Pose, parent: usize, search: usize) -> bool {
+    if search == parent {
+        return true;
+    }
+
+    let mut p = pose.parents[search];
+    // walk up the hierarchy until the root joint is reached
+    while p >= 0 {
+        if p as usize == parent {
+            return true;
+        }
+        p = pose.parents[p as usize];
+    }
+
+    false
+}
+
+/// blend between two poses, a factor of 0.0 gives 'a' and 1.0 gives 'b'
+/// blend_root limits the blend to a joint and its children, -1 blends the whole pose
+/// all three poses are expected to share the same skeleton
+pub fn blend(output: &mut Pose, a: &Pose, b: &Pose, factor: f32, blend_root: i32) {
+    let len = output.joints.len();
+
+    for i in 0..len {
+        if blend_root >= 0 && !is_in_hierarchy(output, blend_root as usize, i) {
+            continue;
+        }
+
+        output.joints[i] = a.joints[i].lerp(&b.joints[i], factor);
+    }
+}
+
+/// same as 'blend' but the factor is scaled by the masks weight for each joint
+pub fn blend_masked(output: &mut Pose, a: &Pose, b: &Pose, factor: f32, mask: &BoneMask) {
+    let len = output.joints.len();
+
+    for i in 0..len {
+        let weight = factor * mask.get_weight(i);
+        if weight <= 0.0 {
+            output.joints[i] = a.joints[i];
+            continue;
+        }
+
+        output.joints[i] = a.joints[i].lerp(&b.joints[i], weight);
+    }
+}
+
+/// samples the first frame of a clip, the result is used as the reference pose for additive blending
+pub fn make_additive_pose(rest_pose: &Pose, clip: &Clip) -> Pose {
+    let mut result = rest_pose.clone();
+    clip.sample(&mut result, clip.get_start_time());
+
+    result
+}
+
+/// add the difference between 'add_pose' and 'base_pose' on top of 'in_pose'
+/// weight scales the difference, 0.0 leaves 'in_pose' untouched and 1.0 adds all of it
+/// blend_root limits the addition to a joint and its children, -1 adds to the whole pose
+pub fn add(
+    output: &mut Pose,
+    in_pose: &Pose,
+    add_pose: &Pose,
+    base_pose: &Pose,
+    weight: f32,
+    blend_root: i32,
+) {
+    let len = add_pose.joints.len();
+
+    for i in 0..len {
+        if blend_root >= 0 && !is_in_hierarchy(add_pose, blend_root as usize, i) {
+            continue;
+        }
+
+        let input = &in_pose.joints[i];
+        let additive = &add_pose.joints[i];
+        let base = &base_pose.joints[i];
+
+        let translation = (additive.translation - base.translation) * weight;
+        let scaling = (additive.scaling - base.scaling) * weight;
+
+        // only the rotation relative to the base is added
+        let mut rotation = base.orientation.inverse() * additive.orientation;
+        // keep it in the same neighborhood as the identity before scaling by the weight
+        if rotation.s < 0.0 {
+            rotation = -rotation;
+        }
+        rotation = Quat::ZERO.nlerp(rotation, weight);
+
+        output.joints[i] = Transform {
+            translation: input.translation + translation,
+            scaling: input.scaling + scaling,
+            orientation: (input.orientation * rotation).unit(),
+        };
+    }
+}
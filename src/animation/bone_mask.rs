@@ -0,0 +1,177 @@
+use crate::src::animation::animator::Animator;
+use crate::src::animation::blending;
+use crate::src::animation::clip::Clip;
+use crate::src::animation::ik;
+use crate::src::animation::pose::Pose;
+use crate::src::skeleton::Skeleton;
+
+use std::rc::Rc;
+
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// bone masks
+// per joint weights that limit what a clip or a blend is allowed to touch
+// upper body waving while the legs keep walking etc...
+
+/// one weight per joint, 0.0 leaves the joint alone and 1.0 lets it be fully overwritten
+#[derive(Clone)]
+pub struct BoneMask {
+    pub weights: Vec<f32>,
+}
+
+impl BoneMask {
+    /// masks out every joint
+    pub fn new(joint_count: usize) -> Self {
+        Self {
+            weights: vec![0.0; joint_count],
+        }
+    }
+
+    /// lets every joint through
+    pub fn full(joint_count: usize) -> Self {
+        Self {
+            weights: vec![1.0; joint_count],
+        }
+    }
+
+    /// mask containing the named joints and everything below them
+    /// unknown names are skipped
+    pub fn from_names(skeleton: &Skeleton, roots: &[&str]) -> Self {
+        let mut mask = Self::new(skeleton.joint_names.len());
+        roots.iter().for_each(|name| {
+            if let Some(joint) = ik::find_joint(skeleton, name) {
+                mask.set_subtree(&skeleton.rest_pose, joint, 1.0);
+            }
+        });
+
+        mask
+    }
+
+    /// set the weight of a joint and all of its children
+    pub fn set_subtree(&mut self, pose: &Pose, root: usize, weight: f32) -> &mut Self {
+        for i in 0..self.weights.len() {
+            if blending::is_in_hierarchy(pose, root, i) {
+                self.weights[i] = weight;
+            }
+        }
+        self
+    }
+
+    pub fn set_weight(&mut self, joint: usize, weight: f32) -> &mut Self {
+        self.weights[joint] = weight;
+        self
+    }
+
+    /// joints outside the mask count as 0.0
+    pub fn get_weight(&self, joint: usize) -> f32 {
+        *self.weights.get(joint).unwrap_or(&0.0)
+    }
+
+    /// swap what is masked in and out, upper body mask -> lower body mask
+    pub fn inverted(&self) -> Self {
+        Self {
+            weights: self.weights.iter().map(|w| 1.0 - w).collect(),
+        }
+    }
+}
+
+/// a clip that overrides part of the pose the base layer produced
+#[derive(Clone)]
+pub struct MaskedLayer {
+    /// playback state, its weight scales every weight in the mask
+    pub animator: Animator,
+    pub mask: BoneMask,
+    rest_pose: Pose,
+    pose: Pose,
+}
+
+impl MaskedLayer {
+    pub fn new(clip: Rc<Clip>, rest_pose: &Pose, mask: BoneMask) -> Self {
+        Self {
+            animator: Animator::new(clip),
+            mask,
+            rest_pose: rest_pose.clone(),
+            pose: rest_pose.clone(),
+        }
+    }
+
+    /// advance the layer and blend it over 'pose'
+    pub fn apply(&mut self, pose: &mut Pose, dt: f32) {
+        if self.animator.weight <= 0.0 {
+            return;
+        }
+
+        // the clip is sampled in full over the rest pose, the mask only gets applied once in the blend
+        self.animator.advance(dt);
+        self.pose.clone_from(&self.rest_pose);
+        self.animator.sample(&mut self.pose);
+
+        let input = pose.clone();
+        blending::blend_masked(pose, &input, &self.pose, self.animator.weight, &self.mask);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::vec3::*;
+    use crate::src::animation::curves::Interpolation;
+    use crate::src::animation::frame::VectorFrame;
+    use crate::src::animation::track_transform::TransformTrack;
+
+    /// moves joints 0, 1 and 2 from x = 0 to x = 2 over a second
+    fn clip() -> Rc<Clip> {
+        let mut clip = Clip::new();
+        for id in 0..3 {
+            let mut track = TransformTrack::new();
+            track.id = id;
+            track.position.interpolation = Interpolation::Linear;
+            for (time, x) in [(0.0, 0.0), (1.0, 2.0)] {
+                let mut frame = VectorFrame::new();
+                frame.time = time;
+                frame.m_value = [x, 0.0, 0.0];
+                track.position.frames.push(frame);
+            }
+            clip.tracks.push(track);
+        }
+        clip.re_calculate_duration();
+        Rc::new(clip)
+    }
+
+    /// three joints all sitting at y = 1
+    fn pose() -> Pose {
+        let mut pose = Pose::new();
+        pose.resize(3);
+        pose.joints
+            .iter_mut()
+            .for_each(|joint| joint.translation = vec3(0.0, 1.0, 0.0));
+        pose
+    }
+
+    #[test]
+    fn masked_sampling_only_touches_the_mask() {
+        let mut mask = BoneMask::new(3);
+        mask.set_weight(0, 1.0).set_weight(1, 0.5);
+
+        let mut pose = pose();
+        clip().sample_masked(&mut pose, 0.5, &mask);
+
+        assert!((pose.joints[0].translation - vec3(1.0, 0.0, 0.0)).len() < 0.0001);
+        assert!((pose.joints[1].translation - vec3(0.5, 0.5, 0.0)).len() < 0.0001);
+        assert!((pose.joints[2].translation - vec3(0.0, 1.0, 0.0)).len() < 0.0001);
+    }
+
+    #[test]
+    fn animator_samples_masked_at_its_own_time() {
+        let mut animator = Animator::new(clip());
+        animator.advance(0.25);
+
+        let mask = BoneMask::new(3).inverted();
+        let mut pose = pose();
+        animator.sample_masked(&mut pose, &mask);
+
+        pose.joints.iter().for_each(|joint| {
+            assert!((joint.translation - vec3(0.5, 0.0, 0.0)).len() < 0.0001);
+        });
+    }
+}
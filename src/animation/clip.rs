@@ -2,41 +2,68 @@
 // _______________________________________________________________________________________________________
 // yet again lots of help from "gabor szauer - hands on c++ game animation programming packt"
 
+use crate::math::quaternion::Quat;
+use crate::src::animation::bone_mask::BoneMask;
 use crate::src::animation::pose::Pose;
+use crate::src::animation::root_motion::{self, RootMotion};
 use crate::src::animation::track_transform::TransformTrack;
+use crate::src::animation::track_weights::WeightTrack;
+use crate::src::transform::Transform;
+
+use std::collections::HashMap;
+
+/// named marker on a clips timeline(footsteps, sound cues etc...)
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationEvent {
+    pub name: String,
+    pub time: f32,
+}
 
 #[derive(Clone)]
 pub struct Clip {
     pub tracks: Vec<TransformTrack>,
+    /// morph target weights
+    pub weight_tracks: Vec<WeightTrack>,
     pub name: String,
+    pub events: Vec<AnimationEvent>,
     start_time: f32,
     end_time: f32,
     looping: bool,
+    /// joint whose horizontal movement and yaw are extracted as root motion
+    root_motion: Option<u32>,
 }
 
 impl Clip {
     pub fn new() -> Self {
         Self {
             tracks: Vec::new(),
+            weight_tracks: Vec::new(),
             name: String::from("None"),
+            events: Vec::new(),
             start_time: 0.0,
             end_time: 0.0,
             looping: true,
+            root_motion: None,
         }
     }
 
-    pub fn sample(&mut self, out_pose: &mut Pose, time: f32) -> f32 {
+    pub fn sample(&self, out_pose: &mut Pose, time: f32) -> f32 {
+        self.sample_with(out_pose, time, self.looping)
+    }
+
+    /// same as 'sample' but ignores the clips own looping flag, used by the animator
+    pub fn sample_with(&self, out_pose: &mut Pose, time: f32, looping: bool) -> f32 {
         if self.get_duration() == 0.0 {
             return 0.0;
         }
 
-        let time = self.adjust_time_to_fit_range(time);
+        let time = self.fit_range(time, looping);
 
         let len = self.tracks.len();
         for i in 0..len {
             let j = self.tracks[i].id;
             let local = &out_pose.joints[j as usize];
-            let animated = self.tracks[i].sample(local, time, self.looping);
+            let animated = self.tracks[i].sample(local, time, looping);
 
             out_pose.joints[j as usize] = animated;
         }
@@ -44,9 +71,133 @@ impl Clip {
         time
     }
 
+    /// samples only the joints in the mask onto 'out_pose', joints the mask leaves out are not touched
+    /// partially masked joints are lerped from what 'out_pose' had towards the clip by their weight
+    /// the mask is already applied, so don't blend the result with the same mask again
+    pub fn sample_masked(&self, out_pose: &mut Pose, time: f32, mask: &BoneMask) -> f32 {
+        self.sample_masked_with(out_pose, time, self.looping, mask)
+    }
+
+    /// same as 'sample_masked' but ignores the clips own looping flag, used by the animator
+    pub fn sample_masked_with(
+        &self,
+        out_pose: &mut Pose,
+        time: f32,
+        looping: bool,
+        mask: &BoneMask,
+    ) -> f32 {
+        if self.get_duration() == 0.0 {
+            return 0.0;
+        }
+
+        let time = self.fit_range(time, looping);
+
+        for track in self.tracks.iter() {
+            let j = track.id as usize;
+            let weight = mask.get_weight(j);
+            if weight <= 0.0 {
+                continue;
+            }
+
+            let local = out_pose.joints[j];
+            let animated = track.sample(&local, time, looping);
+
+            out_pose.joints[j] = if weight >= 1.0 {
+                animated
+            } else {
+                local.lerp(&animated, weight)
+            };
+        }
+
+        time
+    }
+
+    pub fn add_event(&mut self, name: &str, time: f32) {
+        self.events.push(AnimationEvent {
+            name: String::from(name),
+            time,
+        });
+    }
+
+    /// events passed when going from 'from' to 'to' in either direction, 'from' already adjusted to fit the range
+    /// 'to' is 'from' plus the step before wrapping, when looping it wraps around once for every whole loop
+    /// the event at 'from' is skipped since the previous update already reported it, unless 'include_from'
+    /// is set(the first update after a restart)
+    pub fn crossed_events(
+        &self,
+        from: f32,
+        to: f32,
+        looping: bool,
+        include_from: bool,
+    ) -> Vec<&AnimationEvent> {
+        let duration = if looping { self.get_duration() } else { 0.0 };
+        let mut events = Vec::new();
+        let (mut from, mut to, mut include_from) = (from, to, include_from);
+
+        if to >= from {
+            while duration > 0.0 && to >= self.end_time {
+                events.extend(self.events_between(from, self.end_time, include_from));
+                from = self.start_time;
+                to -= duration;
+                include_from = true;
+            }
+        } else {
+            while duration > 0.0 && to < self.start_time {
+                events.extend(self.events_between(from, self.start_time, include_from));
+                from = self.end_time;
+                to += duration;
+                include_from = true;
+            }
+        }
+        events.extend(self.events_between(from, to, include_from));
+
+        events
+    }
+
+    /// events from 'from' to 'to' without wrapping, in the order they are passed
+    fn events_between(&self, from: f32, to: f32, include_from: bool) -> Vec<&AnimationEvent> {
+        let (low, high) = if from <= to { (from, to) } else { (to, from) };
+
+        let mut events: Vec<&AnimationEvent> = self
+            .events
+            .iter()
+            .filter(|event| {
+                let t = event.time;
+                if t == from {
+                    include_from
+                } else {
+                    t >= low && t <= high
+                }
+            })
+            .collect();
+
+        events.sort_by(|a, b| a.time.total_cmp(&b.time));
+        if from > to {
+            events.reverse();
+        }
+
+        events
+    }
+
+    /// sample morph target weights, one entry per mesh id
+    pub fn sample_weights(&self, out: &mut HashMap<u32, Vec<f32>>, time: f32) {
+        if self.get_duration() == 0.0 {
+            return;
+        }
+
+        let time = self.adjust_time_to_fit_range(time);
+        self.weight_tracks.iter().for_each(|track| {
+            track.sample(out, time, self.looping);
+        });
+    }
+
     pub fn adjust_time_to_fit_range(&self, time: f32) -> f32 {
+        self.fit_range(time, self.looping)
+    }
+
+    fn fit_range(&self, time: f32, looping: bool) -> f32 {
         let mut time = time;
-        if self.looping {
+        if looping {
             let duration = self.get_duration();
             if duration <= 0.0 {
                 return 0.0;
@@ -95,9 +246,152 @@ impl Clip {
                 }
             }
         }
+
+        for track in self.weight_tracks.iter() {
+            if track.is_valid() {
+                let start_time = track.get_start_time();
+                let end_time = track.get_end_time();
+
+                if start_time < self.start_time || !start_set {
+                    self.start_time = start_time;
+                    start_set = true;
+                }
+
+                if end_time > self.end_time || !end_set {
+                    self.end_time = end_time;
+                    end_set = true;
+                }
+            }
+        }
     }
 
     pub fn get_duration(&self) -> f32 {
         self.end_time - self.start_time
     }
+
+    pub fn get_start_time(&self) -> f32 {
+        self.start_time
+    }
+
+    pub fn get_end_time(&self) -> f32 {
+        self.end_time
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.looping
+    }
+
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// build frame lookup tables for every track so sampling no longer searches for frames
+    /// has to be called again if any of the frames are changed afterwards
+    pub fn optimize(&mut self, samples_per_second: f32) {
+        self.tracks.iter_mut().for_each(|track| {
+            track.update_index_lookup_table(samples_per_second);
+        });
+        self.weight_tracks.iter_mut().for_each(|track| {
+            track.update_index_lookup_table(samples_per_second);
+        });
+    }
+
+    /// optimized copy of the clip, see 'optimize'
+    pub fn optimized(&self, samples_per_second: f32) -> Clip {
+        let mut clip = self.clone();
+        clip.optimize(samples_per_second);
+
+        clip
+    }
+
+    /// enable root motion for the specified joint, None turns it off
+    pub fn set_root_motion(&mut self, joint: Option<u32>) {
+        self.root_motion = joint;
+    }
+
+    pub fn get_root_motion(&self) -> Option<u32> {
+        self.root_motion
+    }
+
+    /// sample only the root motion joint
+    /// the times are already in range, looping would turn the end of the clip back into its start
+    fn sample_root(&self, reference: &Transform, time: f32) -> Transform {
+        match self.root_motion {
+            Some(joint) => match self.tracks.iter().find(|t| t.id == joint) {
+                Some(track) => track.sample(reference, time, false),
+                None => *reference,
+            },
+            None => *reference,
+        }
+    }
+
+    /// call after 'sample' with the time before and after the update(both already adjusted by sample)
+    /// takes the horizontal movement and yaw out of the root joint and returns it
+    pub fn extract_root_motion(&self, pose: &mut Pose, from: f32, to: f32) -> RootMotion {
+        let wraps = if self.looping && to < from { 1 } else { 0 };
+        self.extract_root_motion_wrapped(pose, from, to, wraps)
+    }
+
+    /// same as 'extract_root_motion' with the wrap arounds decided by the caller
+    /// 'wraps' counts how often the update went past the end, negative when it went back past the start
+    /// the root is moved in model space, its parents are taken from the already sampled pose
+    pub fn extract_root_motion_wrapped(
+        &self,
+        pose: &mut Pose,
+        from: f32,
+        to: f32,
+        wraps: i32,
+    ) -> RootMotion {
+        let joint = match self.root_motion {
+            Some(joint) => joint as usize,
+            None => return RootMotion::ZERO,
+        };
+        if self.get_duration() == 0.0 {
+            return RootMotion::ZERO;
+        }
+
+        // the track is in the space of the roots parent, everything below happens in model space
+        let parent = match pose.parents[joint] {
+            p if p >= 0 => pose.get_global_tranform(p as usize),
+            _ => Transform::DEFAULT,
+        };
+        let reference = pose.joints[joint];
+        let sample = |time: f32| Transform::combine(&parent, &self.sample_root(&reference, time));
+
+        let start = sample(self.start_time);
+        let end = sample(self.end_time);
+        let from_sample = sample(from);
+        let to_sample = sample(to);
+        let start_yaw = root_motion::yaw(&start.orientation);
+
+        let motion = if wraps > 0 {
+            // went past the end, on to the end then a full loop for every extra wrap
+            let cycle = RootMotion::between(&start, &end, start_yaw);
+            let mut motion = RootMotion::between(&from_sample, &end, start_yaw);
+            for _ in 1..wraps {
+                motion = motion.then(&cycle);
+            }
+            motion.then(&RootMotion::between(&start, &to_sample, start_yaw))
+        } else if wraps < 0 {
+            // playing backwards, back to the start then a full loop backwards for every extra wrap
+            let cycle = RootMotion::between(&end, &start, start_yaw);
+            let mut motion = RootMotion::between(&from_sample, &start, start_yaw);
+            for _ in 1..-wraps {
+                motion = motion.then(&cycle);
+            }
+            motion.then(&RootMotion::between(&end, &to_sample, start_yaw))
+        } else {
+            RootMotion::between(&from_sample, &to_sample, start_yaw)
+        };
+
+        // pin the root to where it was at the start of the clip, vertical bobbing is kept
+        let mut root = Transform::combine(&parent, &pose.joints[joint]);
+        root.translation.x = start.translation.x;
+        root.translation.z = start.translation.z;
+        let undo_yaw = start_yaw - root_motion::yaw(&root.orientation);
+        root.orientation = (Quat::create(undo_yaw, root_motion::UP) * root.orientation).unit();
+        pose.joints[joint] = Transform::combine(&parent.inverse(), &root);
+
+        motion
+    }
 }
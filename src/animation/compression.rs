@@ -0,0 +1,723 @@
+use crate::math::quaternion::Quat;
+use crate::math::vec3::Vec3;
+use crate::src::animation::clip::{AnimationEvent, Clip};
+use crate::src::animation::curves::Interpolation;
+use crate::src::animation::frame::{Frame, QuaternionFrame, VectorFrame};
+use crate::src::animation::pose::Pose;
+use crate::src::animation::track::{QuatTrack, Track, VectorTrack};
+use crate::src::animation::track_transform::TransformTrack;
+use crate::src::animation::track_weights::WeightTrack;
+use crate::src::transform::Transform;
+
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// offline clip compression
+// step 1: cubic tracks get resampled into linear ones since tangents are a third of every frame
+// step 2: keys that can be rebuilt from their neighbours within a tolerance are thrown away
+// step 3: what's left is quantized, rotations as "smallest three" and positions/scales as 16 bit
+//         values inside the range each track covers
+// a full f32 frame with tangents is 40 bytes for vectors and 52 for quaternions,
+// a quantized key is 4 bytes of time plus 6 or 7 bytes of value
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ErrorMetric {
+    /// tolerance is used as is, world units for positions and scales, radians for rotations
+    Joint,
+    /// tolerance is how far(world units) any joint below the track is allowed to drift
+    /// each joints rotation tolerance gets scaled down by how far its children reach
+    World,
+}
+
+#[derive(Clone, Copy)]
+pub struct CompressionSettings {
+    pub tolerance: f32,
+    pub metric: ErrorMetric,
+    /// rate cubic tracks are resampled at before they are reduced
+    pub samples_per_second: f32,
+}
+
+impl CompressionSettings {
+    pub fn new() -> Self {
+        Self {
+            tolerance: 0.001,
+            metric: ErrorMetric::World,
+            samples_per_second: 30.0,
+        }
+    }
+
+    pub fn set_tolerance(&mut self, tolerance: f32) -> &mut Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn set_metric(&mut self, metric: ErrorMetric) -> &mut Self {
+        self.metric = metric;
+        self
+    }
+
+    pub fn set_samples_per_second(&mut self, samples_per_second: f32) -> &mut Self {
+        self.samples_per_second = samples_per_second;
+        self
+    }
+}
+
+// _______________________________________________________________________________________________________
+// keyframe reduction
+
+fn vector_error(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    (Vec3::from(a) - Vec3::from(b)).len()
+}
+
+/// angle between two rotations in radians
+fn quat_error(a: &[f32; 4], b: &[f32; 4]) -> f32 {
+    let dot = Quat::from(a).unit().dot(&Quat::from(b).unit()).abs();
+    2.0 * dot.min(1.0).acos()
+}
+
+fn lerp_values<const N: usize>(a: &[f32; N], b: &[f32; N], t: f32) -> [f32; N] {
+    let mut result = [0.0; N];
+    // quaternions take the short way around
+    let flip = N == 4 && (0..N).map(|i| a[i] * b[i]).sum::<f32>() < 0.0;
+    for i in 0..N {
+        let b = if flip { -b[i] } else { b[i] };
+        result[i] = a[i] + (b - a[i]) * t;
+    }
+
+    if N == 4 {
+        let len = result.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if len > 0.0 {
+            result.iter_mut().for_each(|v| *v /= len);
+        }
+    }
+
+    result
+}
+
+/// drops keys of a linear or constant track that its neighbours can rebuild within 'tolerance'
+/// cubic tracks are returned untouched, resample them first
+/// error: distance between two values
+pub fn reduce_track<const N: usize, F>(track: &Track<N>, tolerance: f32, error: F) -> Track<N>
+where
+    F: Fn(&[f32; N], &[f32; N]) -> f32,
+{
+    let len = track.frames.len();
+    if len <= 2 || track.interpolation == Interpolation::Cubic {
+        return track.clone();
+    }
+
+    let frames = &track.frames;
+    let mut keep = vec![false; len];
+    keep[0] = true;
+    keep[len - 1] = true;
+
+    // split the worst key of a span off until every span is within tolerance
+    let mut spans = vec![(0, len - 1)];
+    while let Some((first, last)) = spans.pop() {
+        if last - first < 2 {
+            continue;
+        }
+
+        let mut worst = (0.0, first);
+        for i in first + 1..last {
+            let rebuilt = match track.interpolation {
+                Interpolation::Constant => frames[first].m_value,
+                _ => {
+                    let span = frames[last].time - frames[first].time;
+                    let t = if span > 0.0 {
+                        (frames[i].time - frames[first].time) / span
+                    } else {
+                        0.0
+                    };
+                    lerp_values(&frames[first].m_value, &frames[last].m_value, t)
+                }
+            };
+
+            let e = error(&rebuilt, &frames[i].m_value);
+            if e > worst.0 {
+                worst = (e, i);
+            }
+        }
+
+        if worst.0 > tolerance {
+            keep[worst.1] = true;
+            spans.push((first, worst.1));
+            spans.push((worst.1, last));
+        }
+    }
+
+    let mut result = track.clone();
+    result.frames = frames
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(frame, _)| frame.clone())
+        .collect();
+
+    result
+}
+
+pub fn resample_vector_track(track: &VectorTrack, samples_per_second: f32) -> VectorTrack {
+    resample(track, samples_per_second, |time| {
+        track.sample::<Vec3>(time, false).to_array()
+    })
+}
+
+pub fn resample_quat_track(track: &QuatTrack, samples_per_second: f32) -> QuatTrack {
+    resample(track, samples_per_second, |time| {
+        track.sample::<Quat>(time, false).to_array()
+    })
+}
+
+/// evenly spaced linear keys covering the same time range
+fn resample<const N: usize, F>(track: &Track<N>, samples_per_second: f32, sample: F) -> Track<N>
+where
+    F: Fn(f32) -> [f32; N],
+{
+    if track.frames.len() <= 1 || track.interpolation != Interpolation::Cubic {
+        return track.clone();
+    }
+
+    let start = track.get_start_time();
+    let duration = track.get_end_time() - start;
+    let samples = ((duration * samples_per_second).ceil() as usize).max(1) + 1;
+
+    let mut result = Track::<N>::new();
+    result.interpolation = Interpolation::Linear;
+    for i in 0..samples {
+        let time = start + duration * (i as f32 / (samples - 1) as f32);
+        let mut frame = Frame::<N>::new();
+        frame.time = time;
+        frame.m_value = sample(time);
+        result.frames.push(frame);
+    }
+
+    result
+}
+
+/// distance from every joint to the furthest joint below it in the rest pose
+fn joint_reach(rest_pose: &Pose) -> Vec<f32> {
+    let len = rest_pose.joints.len();
+    let positions: Vec<Vec3> = (0..len)
+        .map(|i| rest_pose.get_global_tranform(i).translation)
+        .collect();
+
+    let mut reach = vec![0.0f32; len];
+    for i in 0..len {
+        let mut p = rest_pose.parents[i];
+        // leaves still move the skin around them, use their own bone length for those
+        if p >= 0 {
+            let bone = (positions[i] - positions[p as usize]).len();
+            reach[i] = reach[i].max(bone);
+        }
+        while p >= 0 {
+            let distance = (positions[i] - positions[p as usize]).len();
+            reach[p as usize] = reach[p as usize].max(distance);
+            p = rest_pose.parents[p as usize];
+        }
+    }
+
+    reach.iter().map(|r| r.max(0.0001)).collect()
+}
+
+/// resample and reduce every track of a clip
+/// rest_pose is only used by ErrorMetric::World
+pub fn reduce_clip(clip: &Clip, rest_pose: &Pose, settings: &CompressionSettings) -> Clip {
+    let reach = joint_reach(rest_pose);
+    let tolerance = settings.tolerance;
+    let sps = settings.samples_per_second;
+
+    let mut result = clip.clone();
+    result.tracks.iter_mut().for_each(|track| {
+        // rotating or scaling a joint moves its children by the change times their distance
+        let scaled = match settings.metric {
+            ErrorMetric::Joint => tolerance,
+            ErrorMetric::World => tolerance / *reach.get(track.id as usize).unwrap_or(&1.0),
+        };
+
+        let position = resample_vector_track(&track.position, sps);
+        let rotation = resample_quat_track(&track.rotation, sps);
+        let scaling = resample_vector_track(&track.scaling, sps);
+
+        track.position = reduce_track(&position, tolerance, vector_error);
+        track.rotation = reduce_track(&rotation, scaled, quat_error);
+        track.scaling = reduce_track(&scaling, scaled, vector_error);
+    });
+    result.re_calculate_duration();
+
+    result
+}
+
+// _______________________________________________________________________________________________________
+// quantization
+
+/// largest component is left out and rebuilt from the other three since the quaternion is unit length
+/// the other three are always within +-1/sqrt(2)
+#[derive(Clone, Copy, Debug)]
+pub struct QuantizedQuat {
+    /// which component was left out
+    pub largest: u8,
+    pub values: [u16; 3],
+}
+
+const SQRT_HALF: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+impl QuantizedQuat {
+    pub fn new(q: &Quat) -> Self {
+        let mut values = q.unit().to_array();
+
+        let mut largest = 0;
+        for i in 1..4 {
+            if values[i].abs() > values[largest].abs() {
+                largest = i;
+            }
+        }
+        // q and -q are the same rotation, keep the dropped component positive
+        if values[largest] < 0.0 {
+            values.iter_mut().for_each(|v| *v = -*v);
+        }
+
+        let mut packed = [0; 3];
+        let kept = values.iter().enumerate().filter(|(i, _)| *i != largest);
+        for (packed, (_, value)) in packed.iter_mut().zip(kept) {
+            let normalized = (value / SQRT_HALF * 0.5 + 0.5).clamp(0.0, 1.0);
+            *packed = (normalized * u16::MAX as f32).round() as u16;
+        }
+
+        Self {
+            largest: largest as u8,
+            values: packed,
+        }
+    }
+
+    pub fn get(&self) -> Quat {
+        let largest = self.largest as usize;
+        let mut values = [0.0; 4];
+        let mut sum = 0.0;
+        let kept = values.iter_mut().enumerate().filter(|(i, _)| *i != largest);
+        for ((_, value), packed) in kept.zip(self.values) {
+            let normalized = packed as f32 / u16::MAX as f32;
+            *value = (normalized - 0.5) * 2.0 * SQRT_HALF;
+            sum += *value * *value;
+        }
+        values[largest] = (1.0 - sum).max(0.0).sqrt();
+
+        Quat::from(&values).unit()
+    }
+}
+
+/// the quantized tracks only keep the values, cubic tracks lose their tangents and play back linearly
+fn quantized_interpolation(interpolation: Interpolation) -> Interpolation {
+    match interpolation {
+        Interpolation::Cubic => Interpolation::Linear,
+        other => other,
+    }
+}
+
+/// 16 bits per component inside the range the track covers
+#[derive(Clone)]
+pub struct QuantizedVectorTrack {
+    pub times: Vec<f32>,
+    pub values: Vec<[u16; 3]>,
+    pub min: Vec3,
+    pub extent: Vec3,
+    pub interpolation: Interpolation,
+}
+
+impl QuantizedVectorTrack {
+    pub fn new(track: &VectorTrack) -> Self {
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        track.frames.iter().for_each(|frame| {
+            for i in 0..3 {
+                min[i] = min[i].min(frame.m_value[i]);
+                max[i] = max[i].max(frame.m_value[i]);
+            }
+        });
+        if track.frames.is_empty() {
+            min = [0.0; 3];
+            max = [0.0; 3];
+        }
+        let extent = [max[0] - min[0], max[1] - min[1], max[2] - min[2]];
+
+        let values = track
+            .frames
+            .iter()
+            .map(|frame| {
+                let mut packed = [0; 3];
+                for i in 0..3 {
+                    let normalized = if extent[i] > 0.0 {
+                        (frame.m_value[i] - min[i]) / extent[i]
+                    } else {
+                        0.0
+                    };
+                    packed[i] = (normalized.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16;
+                }
+                packed
+            })
+            .collect();
+
+        Self {
+            times: track.frames.iter().map(|frame| frame.time).collect(),
+            values,
+            min: Vec3::from(&min),
+            extent: Vec3::from(&extent),
+            interpolation: quantized_interpolation(track.interpolation),
+        }
+    }
+
+    pub fn get(&self, i: usize) -> Vec3 {
+        let v = self.values[i];
+        let n = |x: u16| x as f32 / u16::MAX as f32;
+
+        Vec3::from(&[
+            self.min.x + self.extent.x * n(v[0]),
+            self.min.y + self.extent.y * n(v[1]),
+            self.min.z + self.extent.z * n(v[2]),
+        ])
+    }
+
+    pub fn to_track(&self) -> VectorTrack {
+        let mut track = VectorTrack::new();
+        track.interpolation = self.interpolation;
+        track.frames = (0..self.times.len())
+            .map(|i| {
+                let mut frame = VectorFrame::new();
+                frame.time = self.times[i];
+                frame.m_value = self.get(i).to_array();
+                frame
+            })
+            .collect();
+
+        track
+    }
+
+    pub fn sample(&self, time: f32) -> Option<Vec3> {
+        let (a, b, t) = find_keys(&self.times, time, self.interpolation)?;
+        Some(self.get(a).mix(self.get(b), t))
+    }
+}
+
+#[derive(Clone)]
+pub struct QuantizedQuatTrack {
+    pub times: Vec<f32>,
+    pub values: Vec<QuantizedQuat>,
+    pub interpolation: Interpolation,
+}
+
+impl QuantizedQuatTrack {
+    pub fn new(track: &QuatTrack) -> Self {
+        Self {
+            times: track.frames.iter().map(|frame| frame.time).collect(),
+            values: track
+                .frames
+                .iter()
+                .map(|frame| QuantizedQuat::new(&Quat::from(&frame.m_value)))
+                .collect(),
+            interpolation: quantized_interpolation(track.interpolation),
+        }
+    }
+
+    pub fn to_track(&self) -> QuatTrack {
+        let mut track = QuatTrack::new();
+        track.interpolation = self.interpolation;
+        track.frames = (0..self.times.len())
+            .map(|i| {
+                let mut frame = QuaternionFrame::new();
+                frame.time = self.times[i];
+                frame.m_value = self.values[i].get().to_array();
+                frame
+            })
+            .collect();
+
+        track
+    }
+
+    pub fn sample(&self, time: f32) -> Option<Quat> {
+        let (a, b, t) = find_keys(&self.times, time, self.interpolation)?;
+        let a = self.values[a].get().to_array();
+        let b = self.values[b].get().to_array();
+
+        Some(Quat::from(&lerp_values(&a, &b, t)))
+    }
+}
+
+/// the two keys around 'time' and how far between them it is, None for an empty track
+fn find_keys(
+    times: &[f32],
+    time: f32,
+    interpolation: Interpolation,
+) -> Option<(usize, usize, f32)> {
+    let len = times.len();
+    if len == 0 {
+        return None;
+    }
+
+    let next = times.partition_point(|&t| t <= time);
+    if next == 0 {
+        return Some((0, 0, 0.0));
+    }
+    if next >= len {
+        return Some((len - 1, len - 1, 0.0));
+    }
+
+    let prev = next - 1;
+    let span = times[next] - times[prev];
+    let t = if interpolation == Interpolation::Constant || span <= 0.0 {
+        0.0
+    } else {
+        (time - times[prev]) / span
+    };
+
+    Some((prev, next, t))
+}
+
+#[derive(Clone)]
+pub struct CompressedTransformTrack {
+    pub id: u32,
+    pub position: QuantizedVectorTrack,
+    pub rotation: QuantizedQuatTrack,
+    pub scaling: QuantizedVectorTrack,
+}
+
+impl CompressedTransformTrack {
+    pub fn new(track: &TransformTrack) -> Self {
+        Self {
+            id: track.id,
+            position: QuantizedVectorTrack::new(&track.position),
+            rotation: QuantizedQuatTrack::new(&track.rotation),
+            scaling: QuantizedVectorTrack::new(&track.scaling),
+        }
+    }
+
+    pub fn to_track(&self) -> TransformTrack {
+        let mut track = TransformTrack::new();
+        track.id = self.id;
+        track.position = self.position.to_track();
+        track.rotation = self.rotation.to_track();
+        track.scaling = self.scaling.to_track();
+
+        track
+    }
+
+    /// same rules as TransformTrack::sample, single key tracks keep the reference value
+    pub fn sample(&self, reference: &Transform, time: f32) -> Transform {
+        let mut result = *reference;
+
+        if self.position.times.len() > 1 {
+            result.translation = self.position.sample(time).unwrap();
+        }
+        if self.rotation.times.len() > 1 {
+            result.orientation = self.rotation.sample(time).unwrap();
+        }
+        if self.scaling.times.len() > 1 {
+            result.scaling = self.scaling.sample(time).unwrap();
+        }
+
+        result
+    }
+}
+
+/// a clip that can be sampled straight from its quantized keys
+#[derive(Clone)]
+pub struct CompressedClip {
+    pub name: String,
+    pub tracks: Vec<CompressedTransformTrack>,
+    /// morph weights are small enough to be kept as they are
+    pub weight_tracks: Vec<WeightTrack>,
+    pub events: Vec<AnimationEvent>,
+    looping: bool,
+    root_motion: Option<u32>,
+    start_time: f32,
+    end_time: f32,
+}
+
+impl CompressedClip {
+    /// resample, reduce then quantize
+    pub fn new(clip: &Clip, rest_pose: &Pose, settings: &CompressionSettings) -> Self {
+        let reduced = reduce_clip(clip, rest_pose, settings);
+
+        Self {
+            name: reduced.name.clone(),
+            tracks: reduced
+                .tracks
+                .iter()
+                .map(CompressedTransformTrack::new)
+                .collect(),
+            weight_tracks: reduced.weight_tracks.clone(),
+            events: reduced.events.clone(),
+            looping: reduced.is_looping(),
+            root_motion: reduced.get_root_motion(),
+            start_time: reduced.get_start_time(),
+            end_time: reduced.get_end_time(),
+        }
+    }
+
+    /// back to a regular clip, tracks come out linear or constant
+    pub fn decompress(&self) -> Clip {
+        let mut clip = Clip::new();
+        clip.name = self.name.clone();
+        clip.tracks = self.tracks.iter().map(|track| track.to_track()).collect();
+        clip.weight_tracks = self.weight_tracks.clone();
+        clip.events = self.events.clone();
+        clip.set_looping(self.looping);
+        clip.set_root_motion(self.root_motion);
+        clip.re_calculate_duration();
+
+        clip
+    }
+
+    pub fn get_duration(&self) -> f32 {
+        self.end_time - self.start_time
+    }
+
+    /// same as Clip::sample
+    pub fn sample(&self, out_pose: &mut Pose, time: f32) -> f32 {
+        let duration = self.get_duration();
+        if duration <= 0.0 {
+            return 0.0;
+        }
+
+        let time = if self.looping {
+            self.start_time + (time - self.start_time).rem_euclid(duration)
+        } else {
+            time.clamp(self.start_time, self.end_time)
+        };
+
+        for track in self.tracks.iter() {
+            let j = track.id as usize;
+            out_pose.joints[j] = track.sample(&out_pose.joints[j], time);
+        }
+
+        time
+    }
+
+    /// rough size of the keyframe data in bytes
+    pub fn memory_size(&self) -> usize {
+        self.tracks
+            .iter()
+            .map(|track| {
+                let vectors = track.position.times.len() + track.scaling.times.len();
+                let rotations = track.rotation.times.len();
+                vectors * (4 + 6) + rotations * (4 + 7)
+            })
+            .sum()
+    }
+}
+
+/// rough size of a clips keyframe data in bytes, to compare against CompressedClip::memory_size
+pub fn clip_memory_size(clip: &Clip) -> usize {
+    clip.tracks
+        .iter()
+        .map(|track| {
+            let vectors = track.position.frames.len() + track.scaling.frames.len();
+            let rotations = track.rotation.frames.len();
+            vectors * std::mem::size_of::<VectorFrame>()
+                + rotations * std::mem::size_of::<QuaternionFrame>()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::vec3::vec3;
+
+    fn linear_track(values: impl Iterator<Item = (f32, [f32; 3])>) -> VectorTrack {
+        let mut track = VectorTrack::new();
+        track.interpolation = Interpolation::Linear;
+        track.frames = values
+            .map(|(time, value)| {
+                let mut frame = VectorFrame::new();
+                frame.time = time;
+                frame.m_value = value;
+                frame
+            })
+            .collect();
+        track
+    }
+
+    #[test]
+    fn smallest_three_round_trip() {
+        let quats = [
+            Quat::ZERO,
+            Quat::create(90.0, vec3(0.0, 1.0, 0.0)),
+            Quat::create(-170.0, vec3(1.0, 2.0, 3.0)),
+            Quat::create(45.0, vec3(-1.0, 0.0, 1.0)),
+            // largest component negative, has to come back as the same rotation
+            Quat::new(0.1, -0.2, 0.3, -0.9),
+        ];
+
+        for q in quats {
+            let unpacked = QuantizedQuat::new(&q).get();
+            assert!(quat_error(&q.unit().to_array(), &unpacked.to_array()) < 0.001);
+        }
+    }
+
+    #[test]
+    fn quantized_vectors_stay_within_a_step() {
+        let track = linear_track((0..10).map(|i| {
+            let t = i as f32;
+            (t * 0.1, [t, -t * 2.5, 4.0])
+        }));
+        let quantized = QuantizedVectorTrack::new(&track).to_track();
+
+        // one step of 16 bits over the biggest range(22.5 units)
+        let step = 22.5 / u16::MAX as f32;
+        for (a, b) in track.frames.iter().zip(quantized.frames.iter()) {
+            assert_eq!(a.time, b.time);
+            assert!(vector_error(&a.m_value, &b.m_value) <= step);
+        }
+    }
+
+    #[test]
+    fn quantized_cubic_tracks_play_back_linearly() {
+        let mut track = linear_track([(0.0, [0.0; 3]), (1.0, [2.0, 4.0, -2.0])].into_iter());
+        track.interpolation = Interpolation::Cubic;
+
+        let quantized = QuantizedVectorTrack::new(&track);
+        assert!(quantized.interpolation == Interpolation::Linear);
+        let half = quantized.sample(0.5).unwrap();
+        assert!((half - vec3(1.0, 2.0, -1.0)).len() < 0.001);
+    }
+
+    #[test]
+    fn reduce_track_collapses_a_straight_line() {
+        let track = linear_track((0..20).map(|i| {
+            let t = i as f32 * 0.1;
+            (t, [t, t * 2.0, -t])
+        }));
+        let reduced = reduce_track(&track, 0.0001, vector_error);
+
+        assert_eq!(reduced.frames.len(), 2);
+        assert_eq!(reduced.frames[0].time, 0.0);
+        assert_eq!(reduced.frames[1].time, 1.9);
+    }
+
+    #[test]
+    fn reduce_track_stays_within_tolerance() {
+        let track = linear_track((0..100).map(|i| {
+            let t = i as f32 * 0.05;
+            (t, [t.sin(), (t * 3.0).cos() * 0.5, 0.0])
+        }));
+
+        let tolerance = 0.01;
+        let reduced = reduce_track(&track, tolerance, vector_error);
+        assert!(reduced.frames.len() < track.frames.len());
+        assert_eq!(reduced.frames.first().unwrap().time, 0.0);
+        assert_eq!(reduced.frames.last().unwrap().time, 99.0 * 0.05);
+
+        // every dropped key can be rebuilt from the ones around it
+        for frame in track.frames.iter() {
+            let rebuilt = reduced.sample::<Vec3>(frame.time, false);
+            assert!(vector_error(&rebuilt.to_array(), &frame.m_value) <= tolerance + 0.0001);
+        }
+    }
+
+    #[test]
+    fn reduce_track_leaves_cubic_tracks_alone() {
+        let mut track = linear_track((0..5).map(|i| (i as f32, [0.0; 3])));
+        track.interpolation = Interpolation::Cubic;
+
+        assert_eq!(reduce_track(&track, 1.0, vector_error).frames.len(), 5);
+    }
+}
@@ -0,0 +1,248 @@
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// post process constraints
+// run on the final pose after every clip has been sampled
+// targets are in world space, same as the ik goals
+
+use crate::math::{quaternion::*, vec3::*};
+use crate::src::animation::ik::{self, IKChain};
+use crate::src::animation::pose::Pose;
+use crate::src::skeleton::Skeleton;
+use crate::src::transform::Transform;
+
+/// turns a head(and optionally the spine below it) towards a point
+/// the rotation is spread down the chain so the neck doesn't have to do all the work
+#[derive(Clone)]
+pub struct LookAt {
+    /// (joint, weight) from the lowest spine joint to the head
+    /// each joint takes 'weight' of the rotation that is still left, the head should be 1.0
+    pub joints: Vec<(usize, f32)>,
+    /// axis the head looks along in its own local space
+    pub forward: Vec3,
+    /// how far the head may turn away from where the animation has it facing(degrees)
+    pub max_angle: f32,
+    pub target: Vec3,
+    /// fades the whole constraint in and out
+    pub weight: f32,
+    pub enabled: bool,
+}
+
+impl LookAt {
+    /// chain from 'root' down to 'head', the weights fall off linearly towards the root
+    pub fn from_chain(skeleton: &Skeleton, root: &str, head: &str) -> Option<Self> {
+        let chain = IKChain::from_names(skeleton, root, head)?;
+        let len = chain.joints.len();
+
+        // the share each joint gets of the total turn grows towards the head
+        // converted to a share of whatever rotation is still left when it's that joints turn
+        let shares: Vec<f32> = (1..=len).map(|i| i as f32).collect();
+        let total: f32 = shares.iter().sum();
+        let mut remaining = 1.0;
+        let joints = chain
+            .joints
+            .iter()
+            .zip(shares)
+            .map(|(&joint, share)| {
+                let share = share / total;
+                let weight = if remaining > 0.0 {
+                    (share / remaining).min(1.0)
+                } else {
+                    1.0
+                };
+                remaining -= share;
+                (joint, weight)
+            })
+            .collect();
+
+        Some(Self {
+            joints,
+            forward: vec3(0.0, 0.0, 1.0),
+            max_angle: 70.0,
+            target: Vec3::ZERO,
+            weight: 1.0,
+            enabled: true,
+        })
+    }
+
+    pub fn set_forward(&mut self, forward: Vec3) -> &mut Self {
+        self.forward = forward;
+        self
+    }
+
+    pub fn set_max_angle(&mut self, max_angle: f32) -> &mut Self {
+        self.max_angle = max_angle;
+        self
+    }
+
+    /// model: the objects transform, used to bring the target into model space
+    pub fn apply(&self, pose: &mut Pose, model: &Transform) {
+        if !self.enabled || self.weight <= 0.0 || self.joints.is_empty() {
+            return;
+        }
+
+        let head = self.joints.last().unwrap().0;
+        let target = model.inverse().transform_point(&self.target);
+
+        let head_global = pose.get_global_tranform(head);
+        let facing = head_global.orientation * self.forward;
+        let wanted = target - head_global.translation;
+        if facing.len() < 0.00001 || wanted.len() < 0.00001 {
+            return;
+        }
+
+        // clamp to the angle limit, then fade by the weight
+        let mut desired = limit_direction(&facing, &wanted, self.max_angle);
+        desired = facing.unit().mix(desired, self.weight);
+
+        for &(joint, weight) in self.joints.iter() {
+            let facing = pose.get_global_tranform(head).orientation * self.forward;
+            let delta = Quat::from_to(&facing, &desired);
+            ik::rotate_world(pose, joint, Quat::ZERO.nlerp(delta, weight));
+        }
+    }
+}
+
+/// points an axis of a joint at a target, guns, spotlights, turrets...
+#[derive(Clone)]
+pub struct Aim {
+    pub joint: usize,
+    /// axis to point at the target in the joints local space
+    pub axis: Vec3,
+    pub target: Vec3,
+    pub weight: f32,
+    pub enabled: bool,
+}
+
+impl Aim {
+    pub fn new(joint: usize, axis: Vec3) -> Self {
+        Self {
+            joint,
+            axis,
+            target: Vec3::ZERO,
+            weight: 1.0,
+            enabled: true,
+        }
+    }
+
+    pub fn from_name(skeleton: &Skeleton, joint: &str, axis: Vec3) -> Option<Self> {
+        Some(Self::new(ik::find_joint(skeleton, joint)?, axis))
+    }
+
+    /// model: the objects transform, used to bring the target into model space
+    pub fn apply(&self, pose: &mut Pose, model: &Transform) {
+        if !self.enabled || self.weight <= 0.0 {
+            return;
+        }
+
+        let target = model.inverse().transform_point(&self.target);
+        let global = pose.get_global_tranform(self.joint);
+
+        let axis = global.orientation * self.axis;
+        let wanted = target - global.translation;
+        if axis.len() < 0.00001 || wanted.len() < 0.00001 {
+            return;
+        }
+
+        let delta = Quat::from_to(&axis, &wanted);
+        ik::rotate_world(pose, self.joint, Quat::ZERO.nlerp(delta, self.weight));
+    }
+}
+
+/// 'to' rotated back towards 'from' until they are at most 'max_angle' degrees apart
+fn limit_direction(from: &Vec3, to: &Vec3, max_angle: f32) -> Vec3 {
+    let from = from.unit();
+    let to = to.unit();
+
+    let angle = dot(&from, &to).clamp(-1.0, 1.0).acos().to_degrees();
+    if angle <= max_angle {
+        return to;
+    }
+
+    let mut axis = cross(&from, &to);
+    if axis.len() < 0.00001 {
+        // facing the opposite way, any perpendicular axis works
+        axis = cross(&from, &vec3(0.0, 1.0, 0.0));
+        if axis.len() < 0.00001 {
+            axis = cross(&from, &vec3(1.0, 0.0, 0.0));
+        }
+    }
+
+    Quat::create(max_angle, axis.unit()) * from
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// spine, neck and head stacked on top of each other
+    fn skeleton() -> Skeleton {
+        let mut skeleton = Skeleton::new();
+        skeleton.joint_names = vec!["spine".into(), "neck".into(), "head".into()];
+        skeleton.rest_pose.resize(3);
+        for joint in 1..3 {
+            skeleton.rest_pose.parents[joint] = joint as i32 - 1;
+            skeleton.rest_pose.joints[joint].translation = vec3(0.0, 1.0, 0.0);
+        }
+
+        skeleton
+    }
+
+    fn angle(a: &Vec3, b: &Vec3) -> f32 {
+        dot(&a.unit(), &b.unit())
+            .clamp(-1.0, 1.0)
+            .acos()
+            .to_degrees()
+    }
+
+    #[test]
+    fn look_at_weights_spread_the_turn_towards_the_head() {
+        let look_at = LookAt::from_chain(&skeleton(), "spine", "head").unwrap();
+
+        let weights: Vec<f32> = look_at.joints.iter().map(|&(_, weight)| weight).collect();
+        assert_eq!(
+            look_at.joints.iter().map(|&(j, _)| j).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert!((weights[0] - 1.0 / 6.0).abs() < 0.0001);
+        assert!((weights[1] - 0.4).abs() < 0.0001);
+        assert!((weights[2] - 1.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn look_at_faces_the_target_within_its_limit() {
+        let skeleton = skeleton();
+        let mut look_at = LookAt::from_chain(&skeleton, "spine", "head").unwrap();
+        // the object stands one unit to the right, so the target is 45 degrees to the heads left
+        let model = Transform {
+            translation: vec3(1.0, 0.0, 0.0),
+            ..Transform::DEFAULT
+        };
+
+        look_at.target = vec3(-2.0, 2.0, 3.0);
+        let mut pose = skeleton.rest_pose.clone();
+        look_at.apply(&mut pose, &model);
+        let head = pose.get_global_tranform(2);
+        let facing = head.orientation * look_at.forward;
+        assert!(angle(&facing, &vec3(-1.0, 0.0, 1.0)) < 0.01);
+
+        // right behind, only turns as far as it may
+        look_at.target = vec3(1.0, 2.0, -5.0);
+        let mut pose = skeleton.rest_pose.clone();
+        look_at.apply(&mut pose, &model);
+        let facing = pose.get_global_tranform(2).orientation * look_at.forward;
+        assert!((angle(&facing, &look_at.forward) - look_at.max_angle).abs() < 0.01);
+    }
+
+    #[test]
+    fn aim_points_its_axis_at_the_target() {
+        let skeleton = skeleton();
+        let mut aim = Aim::from_name(&skeleton, "neck", vec3(0.0, 0.0, 1.0)).unwrap();
+        aim.target = vec3(3.0, 4.0, 0.0);
+
+        let mut pose = skeleton.rest_pose.clone();
+        aim.apply(&mut pose, &Transform::DEFAULT);
+        let neck = pose.get_global_tranform(1);
+        let axis = neck.orientation * aim.axis;
+        assert!(angle(&axis, &(aim.target - neck.translation)) < 0.01);
+    }
+}
@@ -0,0 +1,319 @@
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// fading from one clip to another instead of snapping between them
+// yet again lots of help from "gabor szauer - hands on c++ game animation programming packt"
+// clips are referenced by their index into the objects animation list
+
+use crate::src::animation::animator::Animator;
+use crate::src::animation::blending;
+use crate::src::animation::clip::{AnimationEvent, Clip};
+use crate::src::animation::pose::Pose;
+use crate::src::animation::root_motion::RootMotion;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// a clip currently being faded in
+#[derive(Clone)]
+pub struct CrossFadeTarget {
+    pub pose: Pose,
+    pub clip: usize,
+    pub animator: Animator,
+    pub duration: f32,
+    pub elapsed: f32,
+}
+
+#[derive(Clone)]
+pub struct CrossFadeController {
+    targets: Vec<CrossFadeTarget>,
+    clip: Option<usize>,
+    animator: Option<Animator>,
+    pose: Pose,
+    /// movement extracted from the clips during the last update
+    root_motion: RootMotion,
+    /// (clip, event) for every event passed during the last update
+    events: Vec<(usize, AnimationEvent)>,
+    /// morph target weights for each mesh id
+    weights: HashMap<u32, Vec<f32>>,
+}
+
+impl CrossFadeController {
+    pub fn new() -> Self {
+        Self {
+            targets: Vec::new(),
+            clip: None,
+            animator: None,
+            pose: Pose::new(),
+            root_motion: RootMotion::ZERO,
+            events: Vec::new(),
+            weights: HashMap::new(),
+        }
+    }
+
+    /// start playing a clip right away, any fade in progress is thrown away
+    /// the playback speed of the current clip carries over
+    pub fn play(&mut self, clips: &[Rc<Clip>], clip: usize) {
+        let animator = self.next_animator(clips, clip);
+        self.play_animator(clip, animator);
+    }
+
+    /// same as 'play' with an animator set up by the caller(speed, play mode...)
+    pub fn play_animator(&mut self, clip: usize, animator: Animator) {
+        self.targets.clear();
+        self.clip = Some(clip);
+        self.animator = Some(animator);
+    }
+
+    /// fresh animator for 'clip' keeping the speed and weight of whatever played last
+    /// the play mode comes from the clip since looping differs from clip to clip
+    fn next_animator(&self, clips: &[Rc<Clip>], clip: usize) -> Animator {
+        let mut animator = Animator::new(clips[clip].clone());
+        let last = self
+            .targets
+            .last()
+            .map(|target| &target.animator)
+            .or(self.animator.as_ref());
+        if let Some(last) = last {
+            animator.set_speed(last.speed).set_weight(last.weight);
+        }
+
+        animator
+    }
+
+    /// fade from whatever is playing to the specified clip over 'fade_time' seconds
+    /// does nothing if the clip is already playing or is already being faded to
+    /// the playback speed carries over, use 'fade_to_animator' for full control
+    pub fn fade_to(&mut self, clips: &[Rc<Clip>], rest_pose: &Pose, clip: usize, fade_time: f32) {
+        let animator = self.next_animator(clips, clip);
+        self.fade_to_animator(rest_pose, clip, animator, fade_time);
+    }
+
+    /// same as 'fade_to' with an animator set up by the caller, 'clip' is what get_current_clip reports
+    pub fn fade_to_animator(
+        &mut self,
+        rest_pose: &Pose,
+        clip: usize,
+        animator: Animator,
+        fade_time: f32,
+    ) {
+        let current = match self.clip {
+            Some(current) => current,
+            None => {
+                self.play_animator(clip, animator);
+                return;
+            }
+        };
+
+        if let Some(last) = self.targets.last() {
+            if last.clip == clip {
+                return;
+            }
+        } else if current == clip {
+            return;
+        }
+
+        self.targets.push(CrossFadeTarget {
+            pose: rest_pose.clone(),
+            clip,
+            animator,
+            duration: fade_time,
+            elapsed: 0.0,
+        });
+    }
+
+    pub fn update(&mut self, rest_pose: &Pose, dt: f32) {
+        self.events.clear();
+        if self.clip.is_none() {
+            return;
+        }
+
+        // the newest finished fade becomes the current clip
+        // anything older than it is completely covered so it goes too
+        if let Some(i) = self.targets.iter().rposition(|t| t.elapsed >= t.duration) {
+            let target = self.targets.drain(..=i).next_back().unwrap();
+            self.clip = Some(target.clip);
+            self.animator = Some(target.animator);
+        }
+
+        let current = self.clip.unwrap();
+        let animator = self.animator.as_mut().unwrap();
+        self.pose = rest_pose.clone();
+        let previous = animator.advance(dt);
+        animator.sample(&mut self.pose);
+        self.root_motion = animator.extract_root_motion(&mut self.pose, previous);
+        Self::collect_events(&mut self.events, animator, current, previous);
+        self.weights.clear();
+        animator.sample_weights(&mut self.weights);
+
+        for target in self.targets.iter_mut() {
+            let animator = &mut target.animator;
+            let previous = animator.advance(dt);
+            animator.sample(&mut target.pose);
+            let motion = animator.extract_root_motion(&mut target.pose, previous);
+            Self::collect_events(&mut self.events, animator, target.clip, previous);
+            target.elapsed += dt;
+
+            let mut t = 1.0;
+            if target.duration > 0.0 && target.elapsed < target.duration {
+                t = target.elapsed / target.duration;
+            }
+
+            let current_pose = self.pose.clone();
+            blending::blend(&mut self.pose, &current_pose, &target.pose, t, -1);
+            self.root_motion = self.root_motion.lerp(&motion, t);
+
+            let mut weights = HashMap::new();
+            animator.sample_weights(&mut weights);
+            Self::blend_weights(&mut self.weights, &weights, t);
+        }
+    }
+
+    fn collect_events(
+        out: &mut Vec<(usize, AnimationEvent)>,
+        animator: &Animator,
+        index: usize,
+        previous: f32,
+    ) {
+        animator
+            .crossed_events(previous)
+            .into_iter()
+            .for_each(|event| {
+                out.push((index, event.clone()));
+            });
+    }
+
+    fn blend_weights(out: &mut HashMap<u32, Vec<f32>>, other: &HashMap<u32, Vec<f32>>, t: f32) {
+        other.iter().for_each(|(id, weights)| {
+            let current = out.entry(*id).or_default();
+            current.resize(weights.len(), 0.0);
+            for i in 0..weights.len() {
+                current[i] = current[i] + (weights[i] - current[i]) * t;
+            }
+        });
+    }
+
+    /// morph target weights from the last update for each mesh id
+    pub fn get_weights(&self) -> &HashMap<u32, Vec<f32>> {
+        &self.weights
+    }
+
+    /// events passed during the last update as (clip, event)
+    pub fn get_events(&self) -> &[(usize, AnimationEvent)] {
+        &self.events
+    }
+
+    pub fn get_root_motion(&self) -> RootMotion {
+        self.root_motion
+    }
+
+    pub fn get_current_pose(&self) -> &Pose {
+        &self.pose
+    }
+
+    pub fn get_current_clip(&self) -> Option<usize> {
+        self.clip
+    }
+
+    /// playback state of the current clip, change its speed or play mode through here
+    pub fn get_animator(&mut self) -> Option<&mut Animator> {
+        self.animator.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::src::animation::frame::VectorFrame;
+    use crate::src::animation::track_transform::TransformTrack;
+
+    /// moves joint 0 from x = 0 to x = 'to' over a second
+    fn clip(to: f32) -> Rc<Clip> {
+        let mut track = TransformTrack::new();
+        for (time, x) in [(0.0, 0.0), (1.0, to)] {
+            let mut frame = VectorFrame::new();
+            frame.time = time;
+            frame.m_value = [x, 0.0, 0.0];
+            track.position.frames.push(frame);
+        }
+
+        let mut clip = Clip::new();
+        clip.tracks.push(track);
+        clip.re_calculate_duration();
+        Rc::new(clip)
+    }
+
+    fn rest_pose() -> Pose {
+        let mut pose = Pose::new();
+        pose.resize(1);
+        pose
+    }
+
+    #[test]
+    fn fades_keep_the_speed() {
+        let clips = [clip(1.0), clip(2.0)];
+        let rest = rest_pose();
+
+        let mut controller = CrossFadeController::new();
+        controller.play(&clips, 0);
+        controller.get_animator().unwrap().set_speed(2.0);
+
+        controller.fade_to(&clips, &rest, 1, 0.5);
+        controller.update(&rest, 0.6);
+        controller.update(&rest, 0.0);
+
+        assert_eq!(controller.get_current_clip(), Some(1));
+        assert_eq!(controller.get_animator().unwrap().speed, 2.0);
+    }
+
+    #[test]
+    fn every_finished_fade_is_retired_at_once() {
+        let clips = [clip(1.0), clip(2.0), clip(3.0)];
+        let rest = rest_pose();
+
+        let mut controller = CrossFadeController::new();
+        controller.play(&clips, 0);
+        controller.fade_to(&clips, &rest, 1, 0.2);
+        controller.fade_to(&clips, &rest, 2, 0.2);
+        controller.update(&rest, 0.25);
+
+        // both fades are done, the newest one wins and nothing is left fading
+        controller.update(&rest, 0.25);
+        assert_eq!(controller.get_current_clip(), Some(2));
+        assert!(controller.targets.is_empty());
+        let x = controller.get_current_pose().joints[0].translation.x;
+        assert!((x - 3.0 * 0.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn fading_half_way_blends_the_poses() {
+        let clips = [clip(2.0), clip(4.0)];
+        let rest = rest_pose();
+
+        let mut controller = CrossFadeController::new();
+        controller.play(&clips, 0);
+        controller.fade_to(&clips, &rest, 1, 1.0);
+        controller.update(&rest, 0.5);
+
+        // current clip at x = 1, target at x = 2, half way through the fade
+        let x = controller.get_current_pose().joints[0].translation.x;
+        assert!((x - 1.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn fading_without_anything_playing_snaps() {
+        let clips = [clip(1.0), clip(2.0)];
+        let rest = rest_pose();
+
+        let mut controller = CrossFadeController::new();
+        controller.fade_to(&clips, &rest, 1, 1.0);
+        assert_eq!(controller.get_current_clip(), Some(1));
+        assert!(controller.targets.is_empty());
+
+        // already playing or already fading to it, nothing new starts
+        controller.fade_to(&clips, &rest, 1, 1.0);
+        assert!(controller.targets.is_empty());
+        controller.fade_to(&clips, &rest, 0, 1.0);
+        controller.fade_to(&clips, &rest, 0, 1.0);
+        assert_eq!(controller.targets.len(), 1);
+    }
+}
@@ -1,4 +1,7 @@
+use crate::math::{quaternion::Quat, vec3::Vec3};
+
 ///bezier curves
+#[derive(Clone, Copy, Debug)]
 pub struct Bezier<T> {
     ///point 1
     pub p1: T,
@@ -40,6 +43,41 @@ impl Bezier<f32> {
     }
 }
 
+impl Bezier<Vec3> {
+    pub fn interpolate(&self, t: f32) -> Vec3 {
+        let a = self.p1.mix(self.c1, t);
+        let b = self.c2.mix(self.p2, t);
+        let c = self.c1.mix(self.c2, t);
+
+        let d = a.mix(c, t);
+        let e = c.mix(b, t);
+
+        d.mix(e, t)
+    }
+}
+
+/// same construction as the other curves but every step is an nlerp taking the short way around
+impl Bezier<Quat> {
+    pub fn interpolate(&self, t: f32) -> Quat {
+        let a = neighbour_nlerp(self.p1, self.c1, t);
+        let b = neighbour_nlerp(self.c2, self.p2, t);
+        let c = neighbour_nlerp(self.c1, self.c2, t);
+
+        let d = neighbour_nlerp(a, c, t);
+        let e = neighbour_nlerp(c, b, t);
+
+        neighbour_nlerp(d, e, t)
+    }
+}
+
+fn neighbour_nlerp(a: Quat, b: Quat, t: f32) -> Quat {
+    if a.dot(&b) < 0.0 {
+        a.nlerp(-b, t)
+    } else {
+        a.nlerp(b, t)
+    }
+}
+
 #[derive(PartialEq, Clone, Copy)]
 pub enum Interpolation {
     Constant,
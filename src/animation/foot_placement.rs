@@ -0,0 +1,286 @@
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// ground adaptive feet
+// clips are authored against perfectly flat ground, this moves the pelvis and the legs after sampling
+// so the feet end up on whatever is actually under them
+
+use crate::math::{quaternion::*, vec3::*};
+use crate::src::animation::ik::{self, IKChain};
+use crate::src::animation::pose::Pose;
+use crate::src::physics::RayHit;
+use crate::src::skeleton::Skeleton;
+use crate::src::transform::Transform;
+
+const UP: Vec3 = Vec3 {
+    x: 0.0,
+    y: 1.0,
+    z: 0.0,
+};
+
+#[derive(Clone)]
+pub struct FootPlacement {
+    pub pelvis: usize,
+    /// hip, knee and ankle of each leg
+    pub legs: Vec<IKChain>,
+    /// height of the flat ground the clips were made for, in model space
+    /// from_names takes the lowest joint of the rest pose(toes), models aren't always built with their origin on the floor
+    pub ground_offset: f32,
+    /// how far above or below the flat ground a foot is allowed to move(world units)
+    pub max_step: f32,
+    /// rotate feet to match the slope of the ground
+    pub align_feet: bool,
+    pub enabled: bool,
+}
+
+impl FootPlacement {
+    /// legs: (hip, knee, ankle) joint names for each leg
+    pub fn from_names(
+        skeleton: &Skeleton,
+        pelvis: &str,
+        legs: &[(&str, &str, &str)],
+    ) -> Option<Self> {
+        let pelvis = ik::find_joint(skeleton, pelvis)?;
+
+        let mut chains = Vec::new();
+        for (hip, knee, ankle) in legs {
+            let joints = vec![
+                ik::find_joint(skeleton, hip)?,
+                ik::find_joint(skeleton, knee)?,
+                ik::find_joint(skeleton, ankle)?,
+            ];
+            chains.push(IKChain::new(joints));
+        }
+
+        let rest_pose = &skeleton.rest_pose;
+        let ground_offset = (0..rest_pose.joints.len())
+            .map(|joint| rest_pose.get_global_tranform(joint).translation.y)
+            .fold(f32::MAX, f32::min);
+
+        Some(Self {
+            pelvis,
+            legs: chains,
+            ground_offset,
+            max_step: 2.0,
+            align_feet: true,
+            enabled: true,
+        })
+    }
+
+    /// raycast: (origin, direction, max distance) returns the closest hit in world space
+    pub fn apply<F>(&self, pose: &mut Pose, model: &Transform, raycast: F)
+    where
+        F: Fn(Vec3, Vec3, f32) -> Option<RayHit>,
+    {
+        if !self.enabled || self.legs.is_empty() {
+            return;
+        }
+
+        let to_model = model.inverse();
+        let ground = model.transform_point(&vec3(0.0, self.ground_offset, 0.0)).y;
+        let hips = model.transform_point(&pose.get_global_tranform(self.pelvis).translation);
+
+        // the rays start at the height of the hips so steps up to them get found
+        // and reach 'max_step' below the flat ground
+        let distance = hips.y - ground + self.max_step;
+        let hits: Vec<Option<RayHit>> = self
+            .legs
+            .iter()
+            .map(|leg| {
+                let ankle = pose.get_global_tranform(leg.joints[2]).translation;
+                let ankle = model.transform_point(&ankle);
+                let origin = vec3(ankle.x, hips.y, ankle.z);
+
+                raycast(origin, -UP, distance.max(0.0))
+            })
+            .collect();
+
+        // find out how far each foot has to move up or down
+        let offsets: Vec<f32> = hits
+            .iter()
+            .map(|hit| match hit {
+                Some(hit) => (hit.point.y - ground).clamp(-self.max_step, self.max_step),
+                None => 0.0,
+            })
+            .collect();
+
+        // the pelvis follows the lowest foot, up onto a step or down into a dip
+        // following a higher foot would just stretch the other leg, the legs bend for the rest
+        let pelvis_offset = offsets.iter().copied().fold(f32::MAX, f32::min);
+        if pelvis_offset != 0.0 {
+            let delta = world_to_model_dir(&to_model, UP * pelvis_offset);
+            translate_global(pose, self.pelvis, delta);
+        }
+
+        for (i, leg) in self.legs.iter().enumerate() {
+            let hip = pose.get_global_tranform(leg.joints[0]).translation;
+            let knee = pose.get_global_tranform(leg.joints[1]).translation;
+            let ankle = pose.get_global_tranform(leg.joints[2]).translation;
+
+            // the pelvis already moved the foot by pelvis_offset
+            let delta = world_to_model_dir(&to_model, UP * (offsets[i] - pelvis_offset));
+            let target = ankle + delta;
+            // keep bending the knee the way the animation had it
+            let pole = knee + (knee - (hip + ankle) * 0.5);
+
+            ik::solve_two_bone(
+                pose,
+                leg.joints[0],
+                leg.joints[1],
+                leg.joints[2],
+                target,
+                pole,
+            );
+
+            if let (true, Some(hit)) = (self.align_feet, hits[i]) {
+                let up = world_to_model_dir(&to_model, UP);
+                let normal = world_to_model_dir(&to_model, hit.normal);
+                if up.len() > 0.0 && normal.len() > 0.0 {
+                    ik::rotate_world(pose, leg.joints[2], Quat::from_to(&up, &normal));
+                }
+            }
+        }
+    }
+}
+
+fn world_to_model_dir(to_model: &Transform, dir: Vec3) -> Vec3 {
+    to_model.transform_point(&dir) - to_model.transform_point(&Vec3::ZERO)
+}
+
+/// move a joint by a model space offset
+fn translate_global(pose: &mut Pose, joint: usize, delta: Vec3) {
+    let position = pose.get_global_tranform(joint).translation + delta;
+
+    let parent = pose.parents[joint];
+    pose.joints[joint].translation = if parent >= 0 {
+        let parent = pose.get_global_tranform(parent as usize);
+        parent.inverse().transform_point(&position)
+    } else {
+        position
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.001
+    }
+
+    /// pelvis 2 units up with two legs reaching down to y = 0, knees bent slightly forwards
+    fn skeleton() -> Skeleton {
+        let mut skeleton = Skeleton::new();
+        let pose = &mut skeleton.rest_pose;
+        pose.resize(7);
+
+        let joints = [
+            (-1, vec3(0.0, 2.0, 0.0)),
+            (0, vec3(0.3, 0.0, 0.0)),
+            (1, vec3(0.0, -1.0, 0.1)),
+            (2, vec3(0.0, -1.0, -0.1)),
+            (0, vec3(-0.3, 0.0, 0.0)),
+            (4, vec3(0.0, -1.0, 0.1)),
+            (5, vec3(0.0, -1.0, -0.1)),
+        ];
+        for (i, (parent, translation)) in joints.into_iter().enumerate() {
+            pose.parents[i] = parent;
+            pose.joints[i].translation = translation;
+        }
+        skeleton.joint_names = ["Hips", "LeftUpLeg", "LeftLeg", "LeftFoot"]
+            .iter()
+            .chain(["RightUpLeg", "RightLeg", "RightFoot"].iter())
+            .map(|name| name.to_string())
+            .collect();
+
+        skeleton
+    }
+
+    fn feet(skeleton: &Skeleton) -> FootPlacement {
+        let mut feet = FootPlacement::from_names(
+            skeleton,
+            "Hips",
+            &[
+                ("LeftUpLeg", "LeftLeg", "LeftFoot"),
+                ("RightUpLeg", "RightLeg", "RightFoot"),
+            ],
+        )
+        .unwrap();
+        feet.align_feet = false;
+        feet
+    }
+
+    /// flat ground at 'left' under x > 0 and at 'right' under x < 0
+    fn ground(left: f32, right: f32) -> impl Fn(Vec3, Vec3, f32) -> Option<RayHit> {
+        move |origin: Vec3, _dir: Vec3, max_distance: f32| {
+            let height = if origin.x > 0.0 { left } else { right };
+            let distance = origin.y - height;
+            (distance >= 0.0 && distance <= max_distance).then_some(RayHit {
+                point: vec3(origin.x, height, origin.z),
+                normal: UP,
+                distance,
+            })
+        }
+    }
+
+    fn ankle_heights(pose: &Pose) -> (f32, f32) {
+        (
+            pose.get_global_tranform(3).translation.y,
+            pose.get_global_tranform(6).translation.y,
+        )
+    }
+
+    #[test]
+    fn ground_comes_from_the_lowest_joint() {
+        let skeleton = skeleton();
+        assert!(close(feet(&skeleton).ground_offset, 0.0));
+    }
+
+    #[test]
+    fn both_feet_on_a_step_raise_the_pelvis() {
+        let skeleton = skeleton();
+        let mut pose = skeleton.rest_pose.clone();
+        feet(&skeleton).apply(&mut pose, &Transform::DEFAULT, ground(0.5, 0.5));
+
+        let (left, right) = ankle_heights(&pose);
+        assert!(close(left, 0.5) && close(right, 0.5));
+        assert!(close(pose.get_global_tranform(0).translation.y, 2.5));
+    }
+
+    #[test]
+    fn one_foot_on_a_step_bends_that_leg() {
+        let skeleton = skeleton();
+        let mut pose = skeleton.rest_pose.clone();
+        feet(&skeleton).apply(&mut pose, &Transform::DEFAULT, ground(0.5, -0.25));
+
+        // the pelvis goes down with the lower foot
+        let (left, right) = ankle_heights(&pose);
+        assert!(close(left, 0.5) && close(right, -0.25));
+        assert!(close(pose.get_global_tranform(0).translation.y, 1.75));
+    }
+
+    #[test]
+    fn ground_is_found_when_the_model_origin_is_not_on_the_floor() {
+        // origin at the pelvis like the alien, scaled and lifted so the ankles land on y = 0
+        let mut skeleton = skeleton();
+        skeleton.rest_pose.joints[0].translation = Vec3::ZERO;
+        let mut model = Transform::DEFAULT;
+        model.scaling = vec3(3.5, 3.5, 3.5);
+        model.translation = vec3(0.0, 7.0, 0.0);
+
+        let mut pose = skeleton.rest_pose.clone();
+        feet(&skeleton).apply(&mut pose, &model, ground(1.75, 1.75));
+
+        let ankle = model.transform_point(&pose.get_global_tranform(3).translation);
+        assert!(close(ankle.y, 1.75));
+    }
+
+    #[test]
+    fn missed_rays_leave_the_pose_alone() {
+        let skeleton = skeleton();
+        let mut pose = skeleton.rest_pose.clone();
+        feet(&skeleton).apply(&mut pose, &Transform::DEFAULT, |_, _, _| None);
+
+        let (left, right) = ankle_heights(&pose);
+        assert!(close(left, 0.0) && close(right, 0.0));
+    }
+}
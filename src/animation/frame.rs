@@ -6,7 +6,6 @@ pub struct Frame<const N: usize> {
     pub time: f32,
 }
 
-#[allow(unused)]
 pub type ScalarFrame = Frame<1>;
 pub type VectorFrame = Frame<3>;
 pub type QuaternionFrame = Frame<4>;
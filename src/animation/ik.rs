@@ -0,0 +1,418 @@
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// inverse kinematics solvers
+// ccd and fabrik are from "gabor szauer - hands on c++ game animation programming packt"
+// two bone solver follows daniel holdens "simple two joint ik" article
+// all solvers work in model space(the space of Pose::get_global_tranform),
+// use Object::world_to_model to bring world space targets in
+
+use crate::math::{misc::clamp, quaternion::*, vec3::*};
+use crate::src::animation::pose::Pose;
+use crate::src::skeleton::Skeleton;
+use crate::src::transform::Transform;
+
+/// joints from the root of the chain to the end effector
+#[derive(Clone)]
+pub struct IKChain {
+    pub joints: Vec<usize>,
+    /// max number of iterations for the iterative solvers
+    pub steps: u32,
+    /// how close the end effector needs to be to the target to count as solved
+    pub threshold: f32,
+}
+
+impl IKChain {
+    pub fn new(joints: Vec<usize>) -> Self {
+        Self {
+            joints,
+            steps: 15,
+            threshold: 0.00001,
+        }
+    }
+
+    /// builds the chain by walking up the hierarchy from 'end' until 'root' is found
+    pub fn from_names(skeleton: &Skeleton, root: &str, end: &str) -> Option<Self> {
+        let root = find_joint(skeleton, root)?;
+        let end = find_joint(skeleton, end)?;
+        let parents = &skeleton.rest_pose.parents;
+
+        let mut joints = vec![end];
+        let mut current = end;
+        while current != root {
+            let parent = parents[current];
+            if parent < 0 {
+                // root isn't an ancestor of end
+                return None;
+            }
+            current = parent as usize;
+            joints.push(current);
+        }
+        joints.reverse();
+
+        Some(Self::new(joints))
+    }
+
+    fn end_effector(&self, pose: &Pose) -> Vec3 {
+        let last = *self.joints.last().unwrap();
+        pose.get_global_tranform(last).translation
+    }
+
+    /// cyclic coordinate descent
+    /// returns true if the end effector reached the target
+    pub fn solve_ccd(&self, pose: &mut Pose, target: Vec3) -> bool {
+        let len = self.joints.len();
+        if len < 2 {
+            return false;
+        }
+
+        let threshold_sq = self.threshold * self.threshold;
+
+        for _ in 0..self.steps {
+            if (target - self.end_effector(pose)).len().powf(2.0) < threshold_sq {
+                return true;
+            }
+
+            for j in (0..len - 1).rev() {
+                let joint = self.joints[j];
+                let effector = self.end_effector(pose);
+                let position = pose.get_global_tranform(joint).translation;
+
+                let to_effector = effector - position;
+                let to_target = target - position;
+                if to_effector.len() < 0.00001 || to_target.len() < 0.00001 {
+                    continue;
+                }
+
+                let delta = Quat::from_to(&to_effector, &to_target);
+                rotate_world(pose, joint, delta);
+
+                if (target - self.end_effector(pose)).len().powf(2.0) < threshold_sq {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// forward and backward reaching inverse kinematics
+    /// returns true if the end effector reached the target
+    pub fn solve_fabrik(&self, pose: &mut Pose, target: Vec3) -> bool {
+        let len = self.joints.len();
+        if len < 2 {
+            return false;
+        }
+
+        let threshold_sq = self.threshold * self.threshold;
+
+        // work on world positions and convert back to rotations once done
+        let mut positions: Vec<Vec3> = self
+            .joints
+            .iter()
+            .map(|&j| pose.get_global_tranform(j).translation)
+            .collect();
+        let lengths: Vec<f32> = (0..len)
+            .map(|i| {
+                if i == 0 {
+                    0.0
+                } else {
+                    (positions[i] - positions[i - 1]).len()
+                }
+            })
+            .collect();
+        let base = positions[0];
+
+        let mut solved = false;
+        for _ in 0..self.steps {
+            if (target - positions[len - 1]).len().powf(2.0) < threshold_sq {
+                solved = true;
+                break;
+            }
+
+            // backward, put the end effector on the target and drag the rest along
+            positions[len - 1] = target;
+            for i in (0..len - 1).rev() {
+                let dir = (positions[i] - positions[i + 1]).unit();
+                positions[i] = positions[i + 1] + dir * lengths[i + 1];
+            }
+
+            // forward, put the root back where it was
+            positions[0] = base;
+            for i in 1..len {
+                let dir = (positions[i] - positions[i - 1]).unit();
+                positions[i] = positions[i - 1] + dir * lengths[i];
+            }
+        }
+
+        // rotate each joint so its child lands on the solved position
+        for i in 0..len - 1 {
+            let joint = self.joints[i];
+            let position = pose.get_global_tranform(joint).translation;
+            let child = pose.get_global_tranform(self.joints[i + 1]).translation;
+
+            let to_child = child - position;
+            let to_desired = positions[i + 1] - position;
+            if to_child.len() < 0.00001 || to_desired.len() < 0.00001 {
+                continue;
+            }
+
+            rotate_world(pose, joint, Quat::from_to(&to_child, &to_desired));
+        }
+
+        if !solved {
+            solved = (target - self.end_effector(pose)).len().powf(2.0) < threshold_sq;
+        }
+
+        solved
+    }
+}
+
+/// analytic solver for a three joint chain(shoulder, elbow, wrist or hip, knee, ankle)
+/// pole is a point in model space the middle joint should bend towards
+pub fn solve_two_bone(
+    pose: &mut Pose,
+    root: usize,
+    mid: usize,
+    end: usize,
+    target: Vec3,
+    pole: Vec3,
+) {
+    let eps = 0.001;
+
+    let a = pose.get_global_tranform(root).translation;
+    let b = pose.get_global_tranform(mid).translation;
+    let c = pose.get_global_tranform(end).translation;
+
+    let lab = (b - a).len();
+    let lcb = (b - c).len();
+    let lat = clamp((target - a).len(), eps, lab + lcb - eps);
+
+    if lab < eps || lcb < eps || (c - a).len() < eps {
+        return;
+    }
+
+    let angle = |u: Vec3, v: Vec3| clamp(dot(&u.unit(), &v.unit()), -1.0, 1.0).acos();
+
+    // current angles
+    let ac_ab_0 = angle(c - a, b - a);
+    let ba_bc_0 = angle(a - b, c - b);
+    // desired angles from the law of cosines
+    let ac_ab_1 = clamp(
+        (lcb * lcb - lab * lab - lat * lat) / (-2.0 * lab * lat),
+        -1.0,
+        1.0,
+    )
+    .acos();
+    let ba_bc_1 = clamp(
+        (lat * lat - lab * lab - lcb * lcb) / (-2.0 * lab * lcb),
+        -1.0,
+        1.0,
+    )
+    .acos();
+
+    // bend inside the plane the chain currently lies in
+    let mut axis = cross(&(c - a), &(b - a));
+    if axis.len() < eps {
+        // chain is completely straight, fall back to the pole to pick a bending direction
+        axis = cross(&(c - a), &(pole - a));
+    }
+    if axis.len() < eps {
+        return;
+    }
+
+    let r0 = Quat::create((ac_ab_1 - ac_ab_0).to_degrees(), axis);
+    let r1 = Quat::create((ba_bc_1 - ba_bc_0).to_degrees(), axis);
+    rotate_world(pose, root, r0);
+    rotate_world(pose, mid, r1);
+
+    // then swing the whole chain so the end lands on the target
+    let c = pose.get_global_tranform(end).translation;
+    if (target - a).len() < eps || (c - a).len() < eps {
+        return;
+    }
+    rotate_world(pose, root, Quat::from_to(&(c - a), &(target - a)));
+
+    // finally twist around the root to target line so the middle joint points at the pole
+    let dir = (target - a).unit();
+    let project = |p: Vec3| {
+        let v = p - a;
+        v - dir * dot(&v, &dir)
+    };
+    let b = project(pose.get_global_tranform(mid).translation);
+    let pole = project(pole);
+    if b.len() > eps && pole.len() > eps {
+        rotate_world(pose, root, Quat::from_to(&b, &pole));
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Solver {
+    Ccd,
+    Fabrik,
+    /// pole in world space, only uses the first three joints of the chain
+    TwoBone(Vec3),
+}
+
+/// a chain that should reach a world space target every frame
+#[derive(Clone)]
+pub struct IKGoal {
+    pub chain: IKChain,
+    pub target: Vec3,
+    pub solver: Solver,
+    pub enabled: bool,
+}
+
+impl IKGoal {
+    pub fn new(chain: IKChain, target: Vec3, solver: Solver) -> Self {
+        Self {
+            chain,
+            target,
+            solver,
+            enabled: true,
+        }
+    }
+
+    /// model: the objects transform, used to bring the target into model space
+    pub fn solve(&self, pose: &mut Pose, model: &Transform) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        let to_model = model.inverse();
+        let target = to_model.transform_point(&self.target);
+
+        match self.solver {
+            Solver::Ccd => self.chain.solve_ccd(pose, target),
+            Solver::Fabrik => self.chain.solve_fabrik(pose, target),
+            Solver::TwoBone(pole) => {
+                let joints = &self.chain.joints;
+                if joints.len() < 3 {
+                    return false;
+                }
+                let pole = to_model.transform_point(&pole);
+                solve_two_bone(pose, joints[0], joints[1], joints[2], target, pole);
+                true
+            }
+        }
+    }
+}
+
+/// exact name first, then ignoring namespaces and case so "Hips" also finds "mixamorig:Hips"
+pub fn find_joint(skeleton: &Skeleton, name: &str) -> Option<usize> {
+    let names = &skeleton.joint_names;
+    names.iter().position(|n| n == name).or_else(|| {
+        let name = strip_namespace(name);
+        names.iter().position(|n| strip_namespace(n) == name)
+    })
+}
+
+/// "mixamorig:Hips" and "mixamorig_Hips" both become "hips"
+pub fn strip_namespace(name: &str) -> String {
+    let name = match name.rfind(':') {
+        Some(i) => &name[i + 1..],
+        None => name,
+    };
+    let name = name.strip_prefix("mixamorig_").unwrap_or(name);
+
+    name.to_lowercase()
+}
+
+/// apply a model space rotation to a joint by changing its local orientation
+pub fn rotate_world(pose: &mut Pose, joint: usize, delta: Quat) {
+    let global = pose.get_global_tranform(joint).orientation;
+
+    let parent = pose.parents[joint];
+    let parent_rotation = if parent >= 0 {
+        pose.get_global_tranform(parent as usize).orientation
+    } else {
+        Quat::ZERO
+    };
+
+    pose.joints[joint].orientation = (parent_rotation.inverse() * delta * global).unit();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// three joints one unit apart along x
+    fn straight_chain() -> Pose {
+        let mut pose = Pose::new();
+        pose.resize(3);
+        pose.parents = vec![-1, 0, 1];
+        pose.joints[1].translation = vec3(1.0, 0.0, 0.0);
+        pose.joints[2].translation = vec3(1.0, 0.0, 0.0);
+        pose
+    }
+
+    fn position(pose: &Pose, joint: usize) -> Vec3 {
+        pose.get_global_tranform(joint).translation
+    }
+
+    /// solvers only rotate, the bones have to keep their length
+    fn assert_lengths_kept(pose: &Pose) {
+        assert!(((position(pose, 1) - position(pose, 0)).len() - 1.0).abs() < 0.001);
+        assert!(((position(pose, 2) - position(pose, 1)).len() - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn ccd_reaches_target() {
+        let mut pose = straight_chain();
+        let mut chain = IKChain::new(vec![0, 1, 2]);
+        chain.steps = 50;
+        chain.threshold = 0.001;
+
+        let target = vec3(1.0, 1.0, 0.0);
+        assert!(chain.solve_ccd(&mut pose, target));
+        assert!((position(&pose, 2) - target).len() < 0.001);
+        assert_lengths_kept(&pose);
+    }
+
+    #[test]
+    fn fabrik_reaches_target() {
+        let mut pose = straight_chain();
+        let mut chain = IKChain::new(vec![0, 1, 2]);
+        chain.steps = 50;
+        chain.threshold = 0.001;
+
+        let target = vec3(0.5, 0.5, 1.0);
+        assert!(chain.solve_fabrik(&mut pose, target));
+        assert!((position(&pose, 2) - target).len() < 0.01);
+        assert_lengths_kept(&pose);
+    }
+
+    #[test]
+    fn out_of_reach_target_stretches_towards_it() {
+        let mut pose = straight_chain();
+        let chain = IKChain::new(vec![0, 1, 2]);
+
+        let target = vec3(0.0, 5.0, 0.0);
+        assert!(!chain.solve_fabrik(&mut pose, target));
+        assert!((position(&pose, 2) - vec3(0.0, 2.0, 0.0)).len() < 0.01);
+    }
+
+    #[test]
+    fn two_bone_reaches_target_and_bends_towards_pole() {
+        let mut pose = straight_chain();
+
+        let target = vec3(1.0, 0.0, 1.0);
+        let pole = vec3(0.0, 1.0, 0.0);
+        solve_two_bone(&mut pose, 0, 1, 2, target, pole);
+
+        assert!((position(&pose, 2) - target).len() < 0.001);
+        assert_lengths_kept(&pose);
+        // both bones are one unit long so the knee ends up straight above the middle of the line
+        assert!((position(&pose, 1) - vec3(0.5, 0.5f32.sqrt(), 0.5)).len() < 0.001);
+    }
+
+    #[test]
+    fn find_joint_ignores_namespaces() {
+        let mut skeleton = Skeleton::new();
+        skeleton.joint_names = vec![String::from("mixamorig:Hips"), String::from("Spine")];
+
+        assert_eq!(find_joint(&skeleton, "mixamorig:Hips"), Some(0));
+        assert_eq!(find_joint(&skeleton, "Hips"), Some(0));
+        assert_eq!(find_joint(&skeleton, "spine"), Some(1));
+        assert_eq!(find_joint(&skeleton, "Head"), None);
+    }
+}
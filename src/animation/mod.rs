@@ -1,7 +1,26 @@
+pub mod additive;
+pub mod animator;
+pub mod blend_space;
 pub mod basic;
+pub mod blending;
+pub mod bone_mask;
 pub mod clip;
+pub mod compression;
+pub mod constraints;
+pub mod crossfade;
 pub mod curves;
+pub mod foot_placement;
 pub mod frame;
+pub mod ik;
 pub mod pose;
+pub mod property;
+pub mod retarget;
+pub mod root_motion;
+pub mod serialize;
+pub mod skinning;
+pub mod spring_bones;
+pub mod state_machine;
 pub mod track;
 pub mod track_transform;
+pub mod track_weights;
+pub mod tween;
@@ -13,7 +13,7 @@ impl Pose {
             parents: Vec::new(),
         }
     }
-    pub fn get_global_tranform(&mut self, i: usize) -> Transform {
+    pub fn get_global_tranform(&self, i: usize) -> Transform {
         let mut result = self.joints[i];
         let mut p = self.parents[i];
 
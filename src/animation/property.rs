@@ -0,0 +1,262 @@
+use crate::math::vec3::Vec3;
+use crate::src::animation::animator::Animator;
+use crate::src::animation::clip::Clip;
+use crate::src::animation::track::VectorTrack;
+use crate::src::animation::track_transform::TransformTrack;
+use crate::src::camera::Camera;
+use crate::src::lights::PointLight;
+use crate::src::shapes::shape::Shape;
+use crate::src::transform::Transform;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// keyframe animation for things that aren't skeletons
+// a single transform track of a clip drives a shape, point light or the camera
+// clips can be built in code or come from gltf node animations, track ids are node indices there
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum AnimationTarget {
+    /// key into the worlds shapes
+    Shape(String),
+    /// index into the point light list, the translation moves it and the color track tints it
+    PointLight(usize),
+    /// translation and rotation, the camera looks down +z when not rotated
+    Camera,
+}
+
+#[derive(Clone)]
+pub struct PropertyAnimation {
+    pub target: AnimationTarget,
+    animator: Animator,
+    /// id of the transform track to play
+    track: u32,
+    /// used for anything the track doesn't animate
+    pub reference: Transform,
+    /// point light color over time, x y z as r g b
+    pub color: Option<VectorTrack>,
+    pub enabled: bool,
+}
+
+impl PropertyAnimation {
+    pub fn new(target: AnimationTarget, clip: Rc<Clip>, track: u32) -> Self {
+        Self {
+            target,
+            animator: Animator::new(clip),
+            track,
+            reference: Transform::DEFAULT,
+            color: None,
+            enabled: true,
+        }
+    }
+
+    /// plays the track of the node called 'node', names being the ones from GltfFile::extract_joint_names
+    pub fn from_node(
+        target: AnimationTarget,
+        clip: Rc<Clip>,
+        names: &[String],
+        node: &str,
+    ) -> Option<Self> {
+        let index = names.iter().position(|name| name == node)? as u32;
+        if !clip.tracks.iter().any(|track| track.id == index) {
+            return None;
+        }
+
+        Some(Self::new(target, clip, index))
+    }
+
+    pub fn set_reference(&mut self, reference: Transform) -> &mut Self {
+        self.reference = reference;
+        self
+    }
+
+    pub fn set_color_track(&mut self, color: VectorTrack) -> &mut Self {
+        self.color = Some(color);
+        self
+    }
+
+    /// use the targets current state for anything the track doesn't animate(a shapes scale for example)
+    pub fn capture_reference(
+        &mut self,
+        shapes: &HashMap<String, Shape>,
+        lights: &[PointLight],
+        camera: &Camera,
+    ) {
+        match &self.target {
+            AnimationTarget::Shape(name) => {
+                if let Some(shape) = shapes.get(name) {
+                    self.reference = shape.transform;
+                }
+            }
+            AnimationTarget::PointLight(index) => {
+                if let Some(light) = lights.get(*index) {
+                    self.reference.translation = light.pos;
+                }
+            }
+            AnimationTarget::Camera => self.reference.translation = camera.pos,
+        }
+    }
+
+    /// speed, play mode etc...
+    pub fn get_animator(&mut self) -> &mut Animator {
+        &mut self.animator
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        if self.enabled {
+            self.animator.advance(dt);
+        }
+    }
+
+    fn get_track(&self) -> Option<&TransformTrack> {
+        self.animator
+            .get_clip()
+            .tracks
+            .iter()
+            .find(|track| track.id == self.track)
+    }
+
+    pub fn sample_transform(&self) -> Transform {
+        let time = self.animator.get_time();
+        let looping = self.animator.is_looping();
+
+        match self.get_track() {
+            Some(track) => track.sample(&self.reference, time, looping),
+            None => self.reference,
+        }
+    }
+
+    pub fn sample_color(&self) -> Option<Vec3> {
+        let color = self.color.as_ref()?;
+        if color.frames.is_empty() {
+            return None;
+        }
+
+        Some(color.sample::<Vec3>(self.animator.get_time(), self.animator.is_looping()))
+    }
+
+    /// write the sampled values into whatever is being animated, missing targets are ignored
+    pub fn apply(
+        &self,
+        shapes: &mut HashMap<String, Shape>,
+        lights: &mut [PointLight],
+        camera: &mut Camera,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let transform = self.sample_transform();
+        match &self.target {
+            AnimationTarget::Shape(name) => {
+                if let Some(shape) = shapes.get_mut(name) {
+                    shape.transform = transform;
+                }
+            }
+            AnimationTarget::PointLight(index) => {
+                if let Some(light) = lights.get_mut(*index) {
+                    light.pos = transform.translation;
+                    if let Some(color) = self.sample_color() {
+                        light.col = color;
+                    }
+                }
+            }
+            AnimationTarget::Camera => {
+                camera.pos = transform.translation;
+                // leave the mouse in control of where the camera looks unless the track says otherwise
+                if self
+                    .get_track()
+                    .is_some_and(|t| t.rotation.frames.len() > 1)
+                {
+                    camera.set_orientation(transform.orientation);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::vec3::vec3;
+    use crate::src::animation::curves::Interpolation;
+    use crate::src::animation::frame::VectorFrame;
+
+    fn track(from: [f32; 3], to: [f32; 3]) -> VectorTrack {
+        let mut track = VectorTrack::new();
+        track.interpolation = Interpolation::Linear;
+        for (time, value) in [(0.0, from), (2.0, to)] {
+            let mut frame = VectorFrame::new();
+            frame.time = time;
+            frame.m_value = value;
+            track.frames.push(frame);
+        }
+
+        track
+    }
+
+    /// node 1("lamp") rises four units over two seconds
+    fn clip() -> Rc<Clip> {
+        let mut lamp = TransformTrack::new();
+        lamp.id = 1;
+        lamp.position = track([0.0, 0.0, 0.0], [0.0, 4.0, 0.0]);
+
+        let mut clip = Clip::new();
+        clip.tracks.push(lamp);
+        clip.re_calculate_duration();
+        Rc::new(clip)
+    }
+
+    fn names() -> Vec<String> {
+        vec!["door".into(), "lamp".into(), "window".into()]
+    }
+
+    #[test]
+    fn missing_nodes_and_tracks_are_none() {
+        let target = AnimationTarget::PointLight(0);
+        assert!(PropertyAnimation::from_node(target.clone(), clip(), &names(), "lamp").is_some());
+        assert!(PropertyAnimation::from_node(target.clone(), clip(), &names(), "roof").is_none());
+        // the node exists but the clip doesn't animate it
+        assert!(PropertyAnimation::from_node(target, clip(), &names(), "door").is_none());
+    }
+
+    #[test]
+    fn lights_move_and_change_color() {
+        let target = AnimationTarget::PointLight(1);
+        let mut animation = PropertyAnimation::from_node(target, clip(), &names(), "lamp").unwrap();
+        animation.set_color_track(track([1.0, 0.0, 0.0], [0.0, 0.0, 1.0]));
+
+        let light = PointLight {
+            pos: vec3(5.0, 5.0, 5.0),
+            col: Vec3::ZERO,
+            intensity: 1.0,
+        };
+        let mut lights = vec![light, light];
+        let mut camera = Camera::new(Vec3::ZERO, vec3(0.0, 1.0, 0.0), Vec3::ZERO, 1.0);
+        let mut shapes = HashMap::new();
+
+        animation.update(0.5);
+        animation.apply(&mut shapes, &mut lights, &mut camera);
+        assert!((lights[1].pos - vec3(0.0, 1.0, 0.0)).len() < 0.0001);
+        assert!((lights[1].col - vec3(0.75, 0.0, 0.25)).len() < 0.0001);
+        // only the targeted light is touched
+        assert_eq!(lights[0].pos, vec3(5.0, 5.0, 5.0));
+    }
+
+    #[test]
+    fn untracked_parts_come_from_the_reference() {
+        let target = AnimationTarget::Shape("lamp".into());
+        let mut animation = PropertyAnimation::new(target, clip(), 1);
+        animation.set_reference(Transform {
+            scaling: vec3(2.0, 2.0, 2.0),
+            ..Transform::DEFAULT
+        });
+
+        animation.update(1.0);
+        let transform = animation.sample_transform();
+        assert!((transform.translation - vec3(0.0, 2.0, 0.0)).len() < 0.0001);
+        assert_eq!(transform.scaling, vec3(2.0, 2.0, 2.0));
+    }
+}
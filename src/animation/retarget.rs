@@ -0,0 +1,328 @@
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// retargeting
+// plays a clip made for one skeleton on another one
+// rotations are moved over as model space offsets from the rest pose, so joints with different
+// local axes still end up moving the same way, bone lengths always come from the target
+// both skeletons are expected to face the same way in their rest pose(gltf: y up, looking down +z)
+
+use crate::math::quaternion::Quat;
+use crate::src::animation::clip::Clip;
+use crate::src::animation::curves::Interpolation;
+use crate::src::animation::frame::{QuaternionFrame, VectorFrame};
+use crate::src::animation::ik::strip_namespace;
+use crate::src::animation::pose::Pose;
+use crate::src::animation::track_transform::TransformTrack;
+use crate::src::skeleton::Skeleton;
+
+#[derive(Clone)]
+pub struct Retargeter {
+    source: Skeleton,
+    target: Skeleton,
+    /// source joint for every target joint
+    map: Vec<Option<usize>>,
+    /// target joint that also gets its translation retargeted(usually the hips)
+    pub root: Option<usize>,
+    /// how often the source clip is sampled when baking the new clip
+    pub samples_per_second: f32,
+}
+
+impl Retargeter {
+    /// joints are matched by name, namespaces like "mixamorig:" or "mixamorig_" are ignored
+    pub fn new(source: &Skeleton, target: &Skeleton) -> Self {
+        let source_names: Vec<String> = source
+            .joint_names
+            .iter()
+            .map(|name| strip_namespace(name))
+            .collect();
+
+        let map = target
+            .joint_names
+            .iter()
+            .map(|name| {
+                let name = strip_namespace(name);
+                source_names.iter().position(|n| *n == name)
+            })
+            .collect();
+
+        let mut retargeter = Self {
+            source: source.clone(),
+            target: target.clone(),
+            map,
+            root: None,
+            samples_per_second: 30.0,
+        };
+
+        // first mapped joint without a mapped ancestor carries the translation
+        retargeter.root = (0..target.joint_names.len())
+            .find(|&j| retargeter.map[j].is_some() && !retargeter.has_mapped_ancestor(j));
+
+        retargeter
+    }
+
+    /// start from a user table of (target joint, source joint) names instead of matching by name
+    pub fn from_mapping(source: &Skeleton, target: &Skeleton, mapping: &[(&str, &str)]) -> Self {
+        let mut retargeter = Self::new(source, target);
+        retargeter.map.iter_mut().for_each(|m| *m = None);
+        mapping.iter().for_each(|(t, s)| {
+            retargeter.map_joint(t, s);
+        });
+        retargeter.root = (0..target.joint_names.len())
+            .find(|&j| retargeter.map[j].is_some() && !retargeter.has_mapped_ancestor(j));
+
+        retargeter
+    }
+
+    /// override or add a single mapping, returns false if either joint doesn't exist
+    pub fn map_joint(&mut self, target: &str, source: &str) -> bool {
+        let t = self.target.joint_names.iter().position(|n| n == target);
+        let s = self.source.joint_names.iter().position(|n| n == source);
+
+        match (t, s) {
+            (Some(t), Some(s)) => {
+                self.map[t] = Some(s);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// stop a target joint from being driven, it will stay in its rest pose
+    pub fn unmap_joint(&mut self, target: &str) {
+        if let Some(t) = self.target.joint_names.iter().position(|n| n == target) {
+            self.map[t] = None;
+        }
+    }
+
+    pub fn set_root(&mut self, target: &str) -> &mut Self {
+        self.root = self.target.joint_names.iter().position(|n| n == target);
+        self
+    }
+
+    pub fn set_samples_per_second(&mut self, samples_per_second: f32) -> &mut Self {
+        self.samples_per_second = samples_per_second;
+        self
+    }
+
+    /// source joint driving a target joint
+    pub fn get_mapping(&self, target: usize) -> Option<usize> {
+        self.map[target]
+    }
+
+    fn has_mapped_ancestor(&self, joint: usize) -> bool {
+        let parents = &self.target.rest_pose.parents;
+        let mut p = parents[joint];
+        while p >= 0 {
+            if self.map[p as usize].is_some() {
+                return true;
+            }
+            p = parents[p as usize];
+        }
+
+        false
+    }
+
+    /// target joints ordered so parents always come before their children
+    fn hierarchy_order(&self) -> Vec<usize> {
+        let parents = &self.target.rest_pose.parents;
+        let depth = |joint: usize| {
+            let mut depth = 0;
+            let mut p = parents[joint];
+            while p >= 0 {
+                depth += 1;
+                p = parents[p as usize];
+            }
+            depth
+        };
+
+        let mut order: Vec<usize> = (0..parents.len()).collect();
+        order.sort_by_key(|&j| depth(j));
+
+        order
+    }
+
+    /// moves an already sampled source pose onto the target skeleton
+    pub fn retarget_pose(&self, source_pose: &Pose, out: &mut Pose) {
+        let source_rest = &self.source.rest_pose;
+        let target_rest = &self.target.rest_pose;
+        *out = target_rest.clone();
+
+        for joint in self.hierarchy_order() {
+            let source = match self.map[joint] {
+                Some(source) => source,
+                None => continue,
+            };
+
+            // how far the source joint rotated away from its rest pose in model space
+            let source_global = source_pose.get_global_tranform(source).orientation;
+            let source_rest_global = source_rest.get_global_tranform(source).orientation;
+            let delta = source_global * source_rest_global.inverse();
+
+            // apply the same offset to the target rest pose, then bring it back to local space
+            let target_global = delta * target_rest.get_global_tranform(joint).orientation;
+            let parent = out.parents[joint];
+            let parent_global = if parent >= 0 {
+                out.get_global_tranform(parent as usize).orientation
+            } else {
+                Quat::ZERO
+            };
+            out.joints[joint].orientation = (parent_global.inverse() * target_global).unit();
+
+            if Some(joint) == self.root {
+                // scale the movement by the difference in hip height so the feet don't slide
+                let source_rest_local = source_rest.joints[source].translation;
+                let target_rest_local = target_rest.joints[joint].translation;
+                let scale = if source_rest_local.y.abs() > 0.0001 {
+                    target_rest_local.y / source_rest_local.y
+                } else {
+                    1.0
+                };
+
+                let moved = source_pose.joints[source].translation - source_rest_local;
+                out.joints[joint].translation = target_rest_local + moved * scale;
+            }
+        }
+    }
+
+    /// bakes a new clip that plays on the target skeleton
+    pub fn retarget_clip(&self, clip: &Clip) -> Clip {
+        let start = clip.get_start_time();
+        let duration = clip.get_duration();
+        let samples = ((duration * self.samples_per_second).ceil() as usize).max(1) + 1;
+
+        let joints: Vec<usize> = (0..self.map.len())
+            .filter(|&j| self.map[j].is_some())
+            .collect();
+
+        let mut tracks: Vec<TransformTrack> = joints
+            .iter()
+            .map(|&j| {
+                let mut track = TransformTrack::new();
+                track.id = j as u32;
+                track.rotation.interpolation = Interpolation::Linear;
+                track.position.interpolation = Interpolation::Linear;
+                track
+            })
+            .collect();
+
+        let mut source_pose = self.source.rest_pose.clone();
+        let mut target_pose = self.target.rest_pose.clone();
+
+        for i in 0..samples {
+            let time = start + duration * (i as f32 / (samples - 1) as f32);
+
+            source_pose.clone_from(&self.source.rest_pose);
+            // sample the very last frame instead of wrapping back to the first one
+            clip.sample_with(&mut source_pose, time, false);
+            self.retarget_pose(&source_pose, &mut target_pose);
+
+            for (track, &joint) in tracks.iter_mut().zip(joints.iter()) {
+                let local = &target_pose.joints[joint];
+
+                let mut rotation = QuaternionFrame::new();
+                rotation.time = time;
+                rotation.m_value = [
+                    local.orientation.x,
+                    local.orientation.y,
+                    local.orientation.z,
+                    local.orientation.s,
+                ];
+                track.rotation.frames.push(rotation);
+
+                if Some(joint) == self.root {
+                    let mut position = VectorFrame::new();
+                    position.time = time;
+                    position.m_value = [
+                        local.translation.x,
+                        local.translation.y,
+                        local.translation.z,
+                    ];
+                    track.position.frames.push(position);
+                }
+            }
+        }
+
+        let mut result = Clip::new();
+        result.name = clip.name.clone();
+        result.tracks = tracks;
+        result.events = clip.events.clone();
+        result.set_looping(clip.is_looping());
+        // keep root motion on the joint that replaced the old root motion joint
+        if let Some(root) = clip.get_root_motion() {
+            let root = self.map.iter().position(|&m| m == Some(root as usize));
+            result.set_root_motion(root.map(|r| r as u32));
+        }
+        result.re_calculate_duration();
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::vec3::*;
+
+    const UP: Vec3 = Vec3 {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    };
+
+    /// hips with a spine above them at 'height', the target ones have their axes turned around
+    fn skeleton(names: &[&str], height: f32, turned: bool) -> Skeleton {
+        let mut skeleton = Skeleton::new();
+        skeleton.joint_names = names.iter().map(|name| name.to_string()).collect();
+        skeleton.rest_pose.resize(names.len());
+        for joint in 1..names.len() {
+            skeleton.rest_pose.parents[joint] = joint as i32 - 1;
+        }
+        skeleton.rest_pose.joints[0].translation = vec3(0.0, height, 0.0);
+        if turned {
+            skeleton.rest_pose.joints[0].orientation = Quat::create(90.0, vec3(1.0, 0.0, 0.0));
+            skeleton.rest_pose.joints[1].orientation = Quat::create(-30.0, vec3(0.0, 0.0, 1.0));
+        }
+
+        skeleton
+    }
+
+    #[test]
+    fn joints_are_matched_by_name_without_namespaces() {
+        let source = skeleton(&["mixamorig:Hips", "mixamorig:Spine"], 1.0, false);
+        let target = skeleton(&["Hips", "Spine", "Tail"], 1.0, false);
+        let retargeter = Retargeter::new(&source, &target);
+
+        assert_eq!(retargeter.get_mapping(0), Some(0));
+        assert_eq!(retargeter.get_mapping(1), Some(1));
+        assert_eq!(retargeter.get_mapping(2), None);
+        assert_eq!(retargeter.root, Some(0));
+    }
+
+    #[test]
+    fn rotations_move_over_in_model_space() {
+        let source = skeleton(&["Hips", "Spine"], 1.0, false);
+        let target = skeleton(&["Hips", "Spine"], 2.0, true);
+        let retargeter = Retargeter::new(&source, &target);
+
+        // the source spine turns around up and the hips step forward
+        let mut source_pose = source.rest_pose.clone();
+        source_pose.joints[1].orientation = Quat::create(45.0, UP);
+        source_pose.joints[0].translation = vec3(1.0, 1.0, 0.0);
+        let mut out = Pose::new();
+        retargeter.retarget_pose(&source_pose, &mut out);
+
+        // the target spine turns the same way in model space even though its axes differ
+        let expected = Quat::create(45.0, UP) * target.rest_pose.get_global_tranform(1).orientation;
+        let spine = out.get_global_tranform(1).orientation;
+        assert!(spine.dot(&expected).abs() > 0.9999);
+        assert!(
+            out.joints[0]
+                .orientation
+                .dot(&target.rest_pose.joints[0].orientation)
+                > 0.9999
+        );
+
+        // the target hips are twice as high so they move twice as far
+        assert!((out.joints[0].translation - vec3(2.0, 2.0, 0.0)).len() < 0.0001);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::math::{quaternion::*, vec3::*};
+use crate::src::transform::Transform;
+
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// root motion
+// the horizontal movement and turning of a clips root joint gets taken out of the pose
+// and handed to the object instead, so characters actually walk instead of sliding back every loop
+// y is assumed to be up in model space
+
+pub const UP: Vec3 = Vec3 {
+    x: 0.0,
+    y: 1.0,
+    z: 0.0,
+};
+
+/// movement for a single update
+#[derive(Clone, Copy, Debug)]
+pub struct RootMotion {
+    /// in the objects model space
+    pub translation: Vec3,
+    /// degrees around the up axis
+    pub yaw: f32,
+}
+
+impl RootMotion {
+    pub const ZERO: Self = Self {
+        translation: Vec3::ZERO,
+        yaw: 0.0,
+    };
+
+    pub fn lerp(&self, other: &Self, factor: f32) -> Self {
+        Self {
+            translation: self.translation.mix(other.translation, factor),
+            yaw: self.yaw + (other.yaw - self.yaw) * factor,
+        }
+    }
+
+    /// chain another motion that happened after this one
+    pub fn then(&self, next: &Self) -> Self {
+        let turn = Quat::create(self.yaw, UP);
+
+        Self {
+            translation: self.translation + turn * next.translation,
+            yaw: self.yaw + next.yaw,
+        }
+    }
+
+    /// motion between two samples of the root joint
+    /// 'reference_yaw' is the yaw the root is held at once the motion is stripped from the pose
+    pub fn between(from: &Transform, to: &Transform, reference_yaw: f32) -> Self {
+        let from_yaw = yaw(&from.orientation);
+        let to_yaw = yaw(&to.orientation);
+
+        // express the movement relative to where the character was facing
+        let moved = horizontal(&to.translation) - horizontal(&from.translation);
+        let facing = Quat::create(reference_yaw - from_yaw, UP);
+
+        Self {
+            translation: facing * moved,
+            yaw: wrap_degrees(to_yaw - from_yaw),
+        }
+    }
+}
+
+/// rotation around the up axis in degrees
+pub fn yaw(q: &Quat) -> f32 {
+    (2.0 * q.y.atan2(q.s)).to_degrees()
+}
+
+pub fn horizontal(v: &Vec3) -> Vec3 {
+    vec3(v.x, 0.0, v.z)
+}
+
+/// keep an angle between -180 and 180 degrees
+pub fn wrap_degrees(angle: f32) -> f32 {
+    let mut angle = angle % 360.0;
+    if angle > 180.0 {
+        angle -= 360.0;
+    }
+    if angle < -180.0 {
+        angle += 360.0;
+    }
+
+    angle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::src::animation::animator::{Animator, PlayMode};
+    use crate::src::animation::clip::Clip;
+    use crate::src::animation::curves::Interpolation;
+    use crate::src::animation::frame::VectorFrame;
+    use crate::src::animation::pose::Pose;
+    use crate::src::animation::track_transform::TransformTrack;
+    use std::rc::Rc;
+
+    /// joint 1 walks one unit along its parents x axis every second
+    fn walk() -> Rc<Clip> {
+        let mut track = TransformTrack::new();
+        track.id = 1;
+        track.position.interpolation = Interpolation::Linear;
+        for (time, x) in [(0.0, 0.0), (1.0, 1.0)] {
+            let mut frame = VectorFrame::new();
+            frame.time = time;
+            frame.m_value = [x, 0.0, 0.0];
+            track.position.frames.push(frame);
+        }
+
+        let mut clip = Clip::new();
+        clip.tracks.push(track);
+        clip.set_root_motion(Some(1));
+        clip.re_calculate_duration();
+        Rc::new(clip)
+    }
+
+    /// the root sits below a parent turned by 'degrees'
+    fn pose(degrees: f32) -> Pose {
+        let mut pose = Pose::new();
+        pose.resize(2);
+        pose.parents[1] = 0;
+        pose.joints[0].orientation = Quat::create(degrees, UP);
+        pose
+    }
+
+    fn advance(animator: &mut Animator, pose: &mut Pose, dt: f32) -> RootMotion {
+        let previous = animator.advance(dt);
+        animator.sample(pose);
+        animator.extract_root_motion(pose, previous)
+    }
+
+    #[test]
+    fn motion_is_taken_in_model_space() {
+        let mut pose = pose(90.0);
+        let mut animator = Animator::new(walk());
+        let motion = advance(&mut animator, &mut pose, 0.5);
+
+        let expected = Quat::create(90.0, UP) * vec3(0.5, 0.0, 0.0);
+        assert!((motion.translation - expected).len() < 0.0001);
+        assert!(motion.yaw.abs() < 0.0001);
+
+        // the root stays where it started in model space
+        let root = pose.get_global_tranform(1);
+        assert!(horizontal(&root.translation).len() < 0.0001);
+    }
+
+    #[test]
+    fn long_updates_count_every_loop() {
+        let mut pose = pose(0.0);
+        let mut animator = Animator::new(walk());
+        let motion = advance(&mut animator, &mut pose, 2.5);
+
+        assert!((animator.get_time() - 0.5).abs() < 0.0001);
+        assert!((motion.translation - vec3(2.5, 0.0, 0.0)).len() < 0.0001);
+    }
+
+    #[test]
+    fn long_updates_count_every_loop_backwards() {
+        let mut pose = pose(0.0);
+        let mut animator = Animator::new(walk());
+        animator.set_mode(PlayMode::Reverse);
+        let motion = advance(&mut animator, &mut pose, 2.5);
+
+        assert!((animator.get_time() - 0.5).abs() < 0.0001);
+        assert!((motion.translation - vec3(-2.5, 0.0, 0.0)).len() < 0.0001);
+    }
+}
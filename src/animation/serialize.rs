@@ -0,0 +1,859 @@
+use crate::math::quaternion::Quat;
+use crate::math::vec3::Vec3;
+use crate::src::animation::clip::Clip;
+use crate::src::animation::curves::Interpolation;
+use crate::src::animation::frame::Frame;
+use crate::src::animation::pose::Pose;
+use crate::src::animation::track::Track;
+use crate::src::animation::track_transform::TransformTrack;
+use crate::src::animation::track_weights::WeightTrack;
+use crate::src::transform::Transform;
+
+use std::path::Path;
+
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// saving and loading clips and poses
+// two flavours of the same layout, a compact little endian binary one for caching
+// and a line based text one that can be read and diffed by hand
+// every file starts with what it holds and the format version it was written with
+//
+// text layout(names always take up the rest of their line so they can contain spaces):
+//   clip 1
+//   name <name>
+//   looping 1
+//   root_motion -1
+//   events <count>
+//   event <time> <name>
+//   tracks <count>
+//   transform <joint id>
+//   position <interpolation> <frame count>
+//   <time> <value...> <in...> <out...>
+//   rotation ...
+//   scaling ...
+//   weight_tracks <count>
+//   weights <mesh id> <target count>
+//   track <interpolation> <frame count>
+//   ...
+//
+//   pose 1
+//   joints <count>
+//   <parent> <translation xyz> <orientation xyzs> <scaling xyz>
+
+/// bump whenever the layout changes, older versions should keep loading
+pub const VERSION: u32 = 1;
+
+const CLIP_MAGIC: &[u8; 4] = b"RCLP";
+const POSE_MAGIC: &[u8; 4] = b"RPSE";
+
+pub fn clip_to_bytes(clip: &Clip) -> Vec<u8> {
+    let mut writer = BinaryWriter::new();
+    writer.bytes.extend_from_slice(CLIP_MAGIC);
+    writer.u32(VERSION);
+    writer.clip(clip);
+
+    writer.bytes
+}
+
+pub fn clip_from_bytes(bytes: &[u8]) -> Result<Clip, String> {
+    let mut reader = BinaryReader::new(bytes);
+    reader.header(CLIP_MAGIC)?;
+
+    reader.clip()
+}
+
+pub fn pose_to_bytes(pose: &Pose) -> Vec<u8> {
+    let mut writer = BinaryWriter::new();
+    writer.bytes.extend_from_slice(POSE_MAGIC);
+    writer.u32(VERSION);
+    writer.pose(pose);
+
+    writer.bytes
+}
+
+pub fn pose_from_bytes(bytes: &[u8]) -> Result<Pose, String> {
+    let mut reader = BinaryReader::new(bytes);
+    reader.header(POSE_MAGIC)?;
+
+    reader.pose()
+}
+
+pub fn clip_to_text(clip: &Clip) -> String {
+    let mut writer = TextWriter::new();
+    writer.line(&format!("clip {VERSION}"));
+    writer.clip(clip);
+
+    writer.text
+}
+
+pub fn clip_from_text(text: &str) -> Result<Clip, String> {
+    let mut reader = TextReader::new(text);
+    reader.header("clip")?;
+
+    reader.clip()
+}
+
+pub fn pose_to_text(pose: &Pose) -> String {
+    let mut writer = TextWriter::new();
+    writer.line(&format!("pose {VERSION}"));
+    writer.pose(pose);
+
+    writer.text
+}
+
+pub fn pose_from_text(text: &str) -> Result<Pose, String> {
+    let mut reader = TextReader::new(text);
+    reader.header("pose")?;
+
+    reader.pose()
+}
+
+/// files ending in .txt are written as text, anything else as binary
+pub fn save_clip(path: &Path, clip: &Clip) -> Result<(), String> {
+    let bytes = if is_text(path) {
+        clip_to_text(clip).into_bytes()
+    } else {
+        clip_to_bytes(clip)
+    };
+
+    std::fs::write(path, bytes).map_err(|e| format!("couldn't save clip: {e}"))
+}
+
+pub fn load_clip(path: &Path) -> Result<Clip, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("couldn't load clip: {e}"))?;
+
+    if is_text(path) {
+        clip_from_text(&String::from_utf8_lossy(&bytes))
+    } else {
+        clip_from_bytes(&bytes)
+    }
+}
+
+pub fn save_pose(path: &Path, pose: &Pose) -> Result<(), String> {
+    let bytes = if is_text(path) {
+        pose_to_text(pose).into_bytes()
+    } else {
+        pose_to_bytes(pose)
+    };
+
+    std::fs::write(path, bytes).map_err(|e| format!("couldn't save pose: {e}"))
+}
+
+pub fn load_pose(path: &Path) -> Result<Pose, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("couldn't load pose: {e}"))?;
+
+    if is_text(path) {
+        pose_from_text(&String::from_utf8_lossy(&bytes))
+    } else {
+        pose_from_bytes(&bytes)
+    }
+}
+
+fn is_text(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "txt")
+}
+
+fn interpolation_id(interpolation: Interpolation) -> u8 {
+    match interpolation {
+        Interpolation::Constant => 0,
+        Interpolation::Linear => 1,
+        Interpolation::Cubic => 2,
+    }
+}
+
+fn interpolation_from_id(id: u8) -> Result<Interpolation, String> {
+    match id {
+        0 => Ok(Interpolation::Constant),
+        1 => Ok(Interpolation::Linear),
+        2 => Ok(Interpolation::Cubic),
+        _ => Err(format!("unknown interpolation {id}")),
+    }
+}
+
+fn interpolation_name(interpolation: Interpolation) -> &'static str {
+    match interpolation {
+        Interpolation::Constant => "constant",
+        Interpolation::Linear => "linear",
+        Interpolation::Cubic => "cubic",
+    }
+}
+
+fn interpolation_from_name(name: &str) -> Result<Interpolation, String> {
+    match name {
+        "constant" => Ok(Interpolation::Constant),
+        "linear" => Ok(Interpolation::Linear),
+        "cubic" => Ok(Interpolation::Cubic),
+        _ => Err(format!("unknown interpolation {name}")),
+    }
+}
+
+/// clip settings that aren't plain public fields
+fn finish_clip(clip: &mut Clip, looping: bool, root_motion: i32) {
+    clip.set_looping(looping);
+    clip.set_root_motion(if root_motion >= 0 {
+        Some(root_motion as u32)
+    } else {
+        None
+    });
+    clip.re_calculate_duration();
+}
+
+// _______________________________________________________________________________________________________
+// binary
+
+pub struct BinaryWriter {
+    pub bytes: Vec<u8>,
+}
+
+impl BinaryWriter {
+    pub fn new() -> Self {
+        Self { bytes: Vec::new() }
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    pub fn track<const N: usize>(&mut self, track: &Track<N>) {
+        self.u8(interpolation_id(track.interpolation));
+        self.u32(track.frames.len() as u32);
+        for frame in track.frames.iter() {
+            self.f32(frame.time);
+            frame.m_value.iter().for_each(|&v| self.f32(v));
+            frame.m_in.iter().for_each(|&v| self.f32(v));
+            frame.m_out.iter().for_each(|&v| self.f32(v));
+        }
+    }
+
+    pub fn transform_track(&mut self, track: &TransformTrack) {
+        self.u32(track.id);
+        self.track(&track.position);
+        self.track(&track.rotation);
+        self.track(&track.scaling);
+    }
+
+    pub fn weight_track(&mut self, track: &WeightTrack) {
+        self.u32(track.id);
+        self.u32(track.weights.len() as u32);
+        track.weights.iter().for_each(|weights| self.track(weights));
+    }
+
+    pub fn clip(&mut self, clip: &Clip) {
+        self.string(&clip.name);
+        self.u8(clip.is_looping() as u8);
+        self.i32(clip.get_root_motion().map_or(-1, |joint| joint as i32));
+
+        self.u32(clip.events.len() as u32);
+        for event in clip.events.iter() {
+            self.f32(event.time);
+            self.string(&event.name);
+        }
+
+        self.u32(clip.tracks.len() as u32);
+        clip.tracks
+            .iter()
+            .for_each(|track| self.transform_track(track));
+
+        self.u32(clip.weight_tracks.len() as u32);
+        clip.weight_tracks
+            .iter()
+            .for_each(|track| self.weight_track(track));
+    }
+
+    pub fn pose(&mut self, pose: &Pose) {
+        self.u32(pose.joints.len() as u32);
+        for (joint, parent) in pose.joints.iter().zip(pose.parents.iter()) {
+            self.i32(*parent);
+            joint
+                .translation
+                .to_array()
+                .iter()
+                .for_each(|&v| self.f32(v));
+            joint
+                .orientation
+                .to_array()
+                .iter()
+                .for_each(|&v| self.f32(v));
+            joint.scaling.to_array().iter().for_each(|&v| self.f32(v));
+        }
+    }
+}
+
+pub struct BinaryReader<'a> {
+    bytes: &'a [u8],
+    cursor: usize,
+    /// version of the data being read
+    pub version: u32,
+}
+
+impl<'a> BinaryReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            cursor: 0,
+            version: VERSION,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.cursor + len > self.bytes.len() {
+            return Err(String::from("unexpected end of data"));
+        }
+        let bytes = &self.bytes[self.cursor..self.cursor + len];
+        self.cursor += len;
+
+        Ok(bytes)
+    }
+
+    /// checks the magic and reads the version
+    pub fn header(&mut self, magic: &[u8; 4]) -> Result<(), String> {
+        if self.take(4)? != magic {
+            return Err(String::from("wrong file type"));
+        }
+        self.version = self.u32()?;
+        if self.version == 0 || self.version > VERSION {
+            return Err(format!("unsupported version {}", self.version));
+        }
+
+        Ok(())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn string(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+
+        String::from_utf8(bytes.to_vec()).map_err(|_| String::from("invalid string"))
+    }
+
+    pub fn track<const N: usize>(&mut self) -> Result<Track<N>, String> {
+        let mut track = Track::<N>::new();
+        track.interpolation = interpolation_from_id(self.u8()?)?;
+
+        let len = self.u32()?;
+        for _ in 0..len {
+            let mut frame = Frame::<N>::new();
+            frame.time = self.f32()?;
+            for i in 0..N {
+                frame.m_value[i] = self.f32()?;
+            }
+            for i in 0..N {
+                frame.m_in[i] = self.f32()?;
+            }
+            for i in 0..N {
+                frame.m_out[i] = self.f32()?;
+            }
+            track.frames.push(frame);
+        }
+
+        Ok(track)
+    }
+
+    pub fn transform_track(&mut self) -> Result<TransformTrack, String> {
+        let mut track = TransformTrack::new();
+        track.id = self.u32()?;
+        track.position = self.track()?;
+        track.rotation = self.track()?;
+        track.scaling = self.track()?;
+
+        Ok(track)
+    }
+
+    pub fn weight_track(&mut self) -> Result<WeightTrack, String> {
+        let mut track = WeightTrack::new();
+        track.id = self.u32()?;
+
+        let len = self.u32()?;
+        for _ in 0..len {
+            track.weights.push(self.track()?);
+        }
+
+        Ok(track)
+    }
+
+    pub fn clip(&mut self) -> Result<Clip, String> {
+        let mut clip = Clip::new();
+        clip.name = self.string()?;
+        let looping = self.u8()? != 0;
+        let root_motion = self.i32()?;
+
+        let events = self.u32()?;
+        for _ in 0..events {
+            let time = self.f32()?;
+            let name = self.string()?;
+            clip.add_event(&name, time);
+        }
+
+        let tracks = self.u32()?;
+        for _ in 0..tracks {
+            clip.tracks.push(self.transform_track()?);
+        }
+
+        let weight_tracks = self.u32()?;
+        for _ in 0..weight_tracks {
+            clip.weight_tracks.push(self.weight_track()?);
+        }
+
+        finish_clip(&mut clip, looping, root_motion);
+
+        Ok(clip)
+    }
+
+    pub fn pose(&mut self) -> Result<Pose, String> {
+        let mut pose = Pose::new();
+
+        let len = self.u32()?;
+        for _ in 0..len {
+            pose.parents.push(self.i32()?);
+
+            let mut values = [0.0; 10];
+            for value in values.iter_mut() {
+                *value = self.f32()?;
+            }
+            pose.joints.push(transform_from_values(&values));
+        }
+
+        Ok(pose)
+    }
+}
+
+/// translation xyz, orientation xyzs, scaling xyz
+fn transform_from_values(values: &[f32; 10]) -> Transform {
+    Transform {
+        translation: Vec3::from(&[values[0], values[1], values[2]]),
+        orientation: Quat::from(&[values[3], values[4], values[5], values[6]]),
+        scaling: Vec3::from(&[values[7], values[8], values[9]]),
+    }
+}
+
+// _______________________________________________________________________________________________________
+// text
+
+pub struct TextWriter {
+    pub text: String,
+}
+
+impl TextWriter {
+    pub fn new() -> Self {
+        Self {
+            text: String::new(),
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        self.text.push_str(line);
+        self.text.push('\n');
+    }
+
+    fn values(&mut self, values: &[f32]) {
+        let line: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        self.line(&line.join(" "));
+    }
+
+    pub fn track<const N: usize>(&mut self, label: &str, track: &Track<N>) {
+        self.line(&format!(
+            "{label} {} {}",
+            interpolation_name(track.interpolation),
+            track.frames.len()
+        ));
+
+        for frame in track.frames.iter() {
+            let mut values = vec![frame.time];
+            values.extend_from_slice(&frame.m_value);
+            values.extend_from_slice(&frame.m_in);
+            values.extend_from_slice(&frame.m_out);
+            self.values(&values);
+        }
+    }
+
+    pub fn transform_track(&mut self, track: &TransformTrack) {
+        self.line(&format!("transform {}", track.id));
+        self.track("position", &track.position);
+        self.track("rotation", &track.rotation);
+        self.track("scaling", &track.scaling);
+    }
+
+    pub fn weight_track(&mut self, track: &WeightTrack) {
+        self.line(&format!("weights {} {}", track.id, track.weights.len()));
+        track
+            .weights
+            .iter()
+            .for_each(|weights| self.track("track", weights));
+    }
+
+    pub fn clip(&mut self, clip: &Clip) {
+        self.line(&format!("name {}", clip.name));
+        self.line(&format!("looping {}", clip.is_looping() as u8));
+        self.line(&format!(
+            "root_motion {}",
+            clip.get_root_motion().map_or(-1, |joint| joint as i32)
+        ));
+
+        self.line(&format!("events {}", clip.events.len()));
+        for event in clip.events.iter() {
+            self.line(&format!("event {} {}", event.time, event.name));
+        }
+
+        self.line(&format!("tracks {}", clip.tracks.len()));
+        clip.tracks
+            .iter()
+            .for_each(|track| self.transform_track(track));
+
+        self.line(&format!("weight_tracks {}", clip.weight_tracks.len()));
+        clip.weight_tracks
+            .iter()
+            .for_each(|track| self.weight_track(track));
+    }
+
+    pub fn pose(&mut self, pose: &Pose) {
+        self.line(&format!("joints {}", pose.joints.len()));
+        for (joint, parent) in pose.joints.iter().zip(pose.parents.iter()) {
+            let mut values = Vec::new();
+            values.extend_from_slice(&joint.translation.to_array());
+            values.extend_from_slice(&joint.orientation.to_array());
+            values.extend_from_slice(&joint.scaling.to_array());
+
+            let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+            self.line(&format!("{parent} {}", values.join(" ")));
+        }
+    }
+}
+
+pub struct TextReader<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    /// line number of the last line read, for error messages
+    line: usize,
+    /// version of the data being read
+    pub version: u32,
+}
+
+impl<'a> TextReader<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            lines: text.lines().enumerate(),
+            line: 0,
+            version: VERSION,
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {message}", self.line)
+    }
+
+    /// next line that isn't empty
+    fn next_line(&mut self) -> Result<&'a str, String> {
+        for (i, line) in self.lines.by_ref() {
+            self.line = i + 1;
+            let line = line.trim();
+            if !line.is_empty() {
+                return Ok(line);
+            }
+        }
+
+        Err(self.error("unexpected end of file"))
+    }
+
+    /// next line split into its keyword and the rest of the line
+    fn keyword(&mut self, keyword: &str) -> Result<&'a str, String> {
+        let line = self.next_line()?;
+        let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+        if word != keyword {
+            return Err(self.error(&format!("expected {keyword} found {word}")));
+        }
+
+        Ok(rest.trim())
+    }
+
+    fn number<T: std::str::FromStr>(&self, text: &str) -> Result<T, String> {
+        text.trim()
+            .parse()
+            .map_err(|_| self.error(&format!("invalid number {text}")))
+    }
+
+    fn numbers(&self, text: &str) -> Result<Vec<f32>, String> {
+        text.split_whitespace()
+            .map(|value| self.number(value))
+            .collect()
+    }
+
+    pub fn header(&mut self, kind: &str) -> Result<(), String> {
+        let version = self.keyword(kind)?;
+        self.version = self.number(version)?;
+        if self.version == 0 || self.version > VERSION {
+            return Err(self.error(&format!("unsupported version {}", self.version)));
+        }
+
+        Ok(())
+    }
+
+    pub fn track<const N: usize>(&mut self, label: &str) -> Result<Track<N>, String> {
+        let rest = self.keyword(label)?;
+        let (interpolation, len) = rest.split_once(' ').unwrap_or((rest, "0"));
+
+        let mut track = Track::<N>::new();
+        track.interpolation = interpolation_from_name(interpolation).map_err(|e| self.error(&e))?;
+
+        let len: usize = self.number(len)?;
+        for _ in 0..len {
+            let line = self.next_line()?;
+            let values = self.numbers(line)?;
+            if values.len() != 1 + 3 * N {
+                return Err(self.error("wrong number of values in frame"));
+            }
+
+            let mut frame = Frame::<N>::new();
+            frame.time = values[0];
+            frame.m_value.copy_from_slice(&values[1..1 + N]);
+            frame.m_in.copy_from_slice(&values[1 + N..1 + 2 * N]);
+            frame.m_out.copy_from_slice(&values[1 + 2 * N..1 + 3 * N]);
+            track.frames.push(frame);
+        }
+
+        Ok(track)
+    }
+
+    pub fn transform_track(&mut self) -> Result<TransformTrack, String> {
+        let mut track = TransformTrack::new();
+        let id = self.keyword("transform")?;
+        track.id = self.number(id)?;
+        track.position = self.track("position")?;
+        track.rotation = self.track("rotation")?;
+        track.scaling = self.track("scaling")?;
+
+        Ok(track)
+    }
+
+    pub fn weight_track(&mut self) -> Result<WeightTrack, String> {
+        let mut track = WeightTrack::new();
+        let rest = self.keyword("weights")?;
+        let (id, len) = rest.split_once(' ').unwrap_or((rest, "0"));
+        track.id = self.number(id)?;
+
+        let len: usize = self.number(len)?;
+        for _ in 0..len {
+            track.weights.push(self.track("track")?);
+        }
+
+        Ok(track)
+    }
+
+    pub fn clip(&mut self) -> Result<Clip, String> {
+        let mut clip = Clip::new();
+        clip.name = self.keyword("name")?.to_string();
+        let looping = self.keyword("looping")?;
+        let looping = self.number::<u8>(looping)? != 0;
+        let root_motion = self.keyword("root_motion")?;
+        let root_motion: i32 = self.number(root_motion)?;
+
+        let events = self.keyword("events")?;
+        let events: usize = self.number(events)?;
+        for _ in 0..events {
+            let rest = self.keyword("event")?;
+            let (time, name) = rest.split_once(' ').unwrap_or((rest, ""));
+            let time: f32 = self.number(time)?;
+            clip.add_event(name.trim(), time);
+        }
+
+        let tracks = self.keyword("tracks")?;
+        let tracks: usize = self.number(tracks)?;
+        for _ in 0..tracks {
+            clip.tracks.push(self.transform_track()?);
+        }
+
+        let weight_tracks = self.keyword("weight_tracks")?;
+        let weight_tracks: usize = self.number(weight_tracks)?;
+        for _ in 0..weight_tracks {
+            clip.weight_tracks.push(self.weight_track()?);
+        }
+
+        finish_clip(&mut clip, looping, root_motion);
+
+        Ok(clip)
+    }
+
+    pub fn pose(&mut self) -> Result<Pose, String> {
+        let mut pose = Pose::new();
+
+        let len = self.keyword("joints")?;
+        let len: usize = self.number(len)?;
+        for _ in 0..len {
+            let line = self.next_line()?;
+            let (parent, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let values = self.numbers(rest)?;
+            if values.len() != 10 {
+                return Err(self.error("wrong number of values in joint"));
+            }
+
+            pose.parents.push(self.number(parent)?);
+            pose.joints
+                .push(transform_from_values(values[..].try_into().unwrap()));
+        }
+
+        Ok(pose)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame<const N: usize>(time: f32, value: [f32; N]) -> Frame<N> {
+        let mut frame = Frame::new();
+        frame.time = time;
+        frame.m_value = value;
+        frame.m_in = value.map(|v| v * 0.5);
+        frame.m_out = value.map(|v| -v);
+        frame
+    }
+
+    fn test_clip() -> Clip {
+        let mut track = TransformTrack::new();
+        track.id = 3;
+        track.position.interpolation = Interpolation::Linear;
+        track.position.frames = vec![frame(0.0, [0.0, 1.0, 2.0]), frame(1.5, [0.1, -3.25, 7.0])];
+        track.rotation.frames = vec![
+            frame(0.0, [0.0, 0.0, 0.0, 1.0]),
+            frame(0.75, [0.0, 0.38268343, 0.0, 0.9238795]),
+        ];
+        track.scaling.interpolation = Interpolation::Constant;
+        track.scaling.frames = vec![frame(0.25, [1.0, 1.0, 1.0])];
+
+        let mut weights = WeightTrack::new();
+        weights.id = 1;
+        let mut target = Track::new();
+        target.frames = vec![frame(0.0, [0.0]), frame(1.0, [1.0 / 3.0])];
+        weights.weights.push(target);
+
+        let mut clip = Clip::new();
+        clip.name = String::from("walk cycle");
+        clip.tracks.push(track);
+        clip.weight_tracks.push(weights);
+        clip.add_event("left foot", 0.5);
+        clip.add_event("right foot", 1.25);
+        clip.set_looping(false);
+        clip.set_root_motion(Some(3));
+        clip.re_calculate_duration();
+        clip
+    }
+
+    fn test_pose() -> Pose {
+        let mut pose = Pose::new();
+        pose.resize(3);
+        pose.parents = vec![-1, 0, 1];
+        pose.joints[1].translation.y = 1.5;
+        pose.joints[1].orientation = Quat::create(
+            30.0,
+            Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        );
+        pose.joints[2].scaling.x = 0.1;
+        pose
+    }
+
+    fn assert_same_track<const N: usize>(a: &Track<N>, b: &Track<N>) {
+        assert!(a.interpolation == b.interpolation);
+        assert_eq!(a.frames.len(), b.frames.len());
+        for (a, b) in a.frames.iter().zip(b.frames.iter()) {
+            assert_eq!(a.time, b.time);
+            assert_eq!(a.m_value, b.m_value);
+            assert_eq!(a.m_in, b.m_in);
+            assert_eq!(a.m_out, b.m_out);
+        }
+    }
+
+    fn assert_same_clip(a: &Clip, b: &Clip) {
+        assert_eq!(a.name, b.name);
+        assert_eq!(a.is_looping(), b.is_looping());
+        assert_eq!(a.get_root_motion(), b.get_root_motion());
+        assert_eq!(a.get_start_time(), b.get_start_time());
+        assert_eq!(a.get_end_time(), b.get_end_time());
+        assert_eq!(a.events, b.events);
+
+        assert_eq!(a.tracks.len(), b.tracks.len());
+        for (a, b) in a.tracks.iter().zip(b.tracks.iter()) {
+            assert_eq!(a.id, b.id);
+            assert_same_track(&a.position, &b.position);
+            assert_same_track(&a.rotation, &b.rotation);
+            assert_same_track(&a.scaling, &b.scaling);
+        }
+
+        assert_eq!(a.weight_tracks.len(), b.weight_tracks.len());
+        for (a, b) in a.weight_tracks.iter().zip(b.weight_tracks.iter()) {
+            assert_eq!(a.id, b.id);
+            assert_eq!(a.weights.len(), b.weights.len());
+            for (a, b) in a.weights.iter().zip(b.weights.iter()) {
+                assert_same_track(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn clip_binary_round_trip() {
+        let clip = test_clip();
+        let loaded = clip_from_bytes(&clip_to_bytes(&clip)).unwrap();
+        assert_same_clip(&clip, &loaded);
+    }
+
+    #[test]
+    fn clip_text_round_trip() {
+        let clip = test_clip();
+        let loaded = clip_from_text(&clip_to_text(&clip)).unwrap();
+        assert_same_clip(&clip, &loaded);
+    }
+
+    #[test]
+    fn pose_binary_round_trip() {
+        let pose = test_pose();
+        assert!(pose_from_bytes(&pose_to_bytes(&pose)).unwrap() == pose);
+    }
+
+    #[test]
+    fn pose_text_round_trip() {
+        let pose = test_pose();
+        assert!(pose_from_text(&pose_to_text(&pose)).unwrap() == pose);
+    }
+
+    #[test]
+    fn wrong_or_broken_data_is_an_error() {
+        let bytes = clip_to_bytes(&test_clip());
+        assert!(pose_from_bytes(&bytes).is_err());
+        assert!(clip_from_bytes(&bytes[..bytes.len() / 2]).is_err());
+        assert!(clip_from_text("pose 1\njoints 0").is_err());
+        assert!(clip_from_text(&format!("clip {}", VERSION + 1)).is_err());
+    }
+}
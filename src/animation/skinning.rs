@@ -0,0 +1,329 @@
+use crate::math::dual_quaternion::DualQuat;
+use crate::math::mat4::Mat4;
+use crate::math::vec3::*;
+use crate::src::model::{Mesh, Model, Vertex};
+
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// cpu skinning
+// same linear blend skinning animation.vert does, but the result ends up back on the cpu
+// for checking the shader against, picking animated characters or saving a posed mesh
+// palette is the matrix list Object::get_pose returns(global joint transform * inverse bind pose)
+
+/// how the joint influences of a vertex get mixed
+/// linear blends matrices, it is cheap and supports scaling but twisting joints collapse(candy wrapper)
+/// dual quaternion keeps the volume on twists but can't scale, scaled palettes fall back to linear
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SkinningMode {
+    Linear,
+    DualQuaternion,
+}
+
+/// deformed vertex data, parallel to the meshes vertices
+#[derive(Clone)]
+pub struct SkinnedMesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+}
+
+/// weighted sum of the joint matrices influencing a vertex
+/// vertices without any weights are left where they are
+pub fn skin_matrix(vertex: &Vertex, palette: &[Mat4]) -> Mat4 {
+    let mut skin = Mat4 {
+        data: [[0.0; 4]; 4],
+    };
+    let mut total = 0.0;
+
+    for i in 0..4 {
+        let id = vertex.bone_ids[i];
+        let weight = vertex.weights[i];
+        if id < 0 || weight == 0.0 || id as usize >= palette.len() {
+            continue;
+        }
+
+        let mat = &palette[id as usize];
+        for r in 0..4 {
+            for c in 0..4 {
+                skin.data[r][c] += mat.data[r][c] * weight;
+            }
+        }
+        total += weight;
+    }
+
+    if total == 0.0 {
+        return Mat4::IDENTITY;
+    }
+
+    skin
+}
+
+/// true when the matrix only rotates and moves, a dual quaternion can hold it without losing anything
+pub fn is_rigid(mat: &Mat4) -> bool {
+    let d = &mat.data;
+    (0..3).all(|c| {
+        let axis = vec3(d[0][c], d[1][c], d[2][c]);
+        (axis.len() - 1.0).abs() < 0.001
+    })
+}
+
+fn transform_point(m: &Mat4, p: &Vec3) -> Vec3 {
+    let d = &m.data;
+    vec3(
+        d[0][0] * p.x + d[0][1] * p.y + d[0][2] * p.z + d[0][3],
+        d[1][0] * p.x + d[1][1] * p.y + d[1][2] * p.z + d[1][3],
+        d[2][0] * p.x + d[2][1] * p.y + d[2][2] * p.z + d[2][3],
+    )
+}
+
+/// multiplies by the inverse transpose of the upper 3x3, which is its cofactor matrix up to a scale
+/// the normal gets normalized afterwards so the scale doesn't matter
+fn transform_normal(m: &Mat4, n: &Vec3) -> Vec3 {
+    let d = &m.data;
+    let cofactor =
+        |r0: usize, r1: usize, c0: usize, c1: usize| d[r0][c0] * d[r1][c1] - d[r0][c1] * d[r1][c0];
+
+    let c = [
+        [
+            cofactor(1, 2, 1, 2),
+            -cofactor(1, 2, 0, 2),
+            cofactor(1, 2, 0, 1),
+        ],
+        [
+            -cofactor(0, 2, 1, 2),
+            cofactor(0, 2, 0, 2),
+            -cofactor(0, 2, 0, 1),
+        ],
+        [
+            cofactor(0, 1, 1, 2),
+            -cofactor(0, 1, 0, 2),
+            cofactor(0, 1, 0, 1),
+        ],
+    ];
+
+    let result = vec3(
+        c[0][0] * n.x + c[0][1] * n.y + c[0][2] * n.z,
+        c[1][0] * n.x + c[1][1] * n.y + c[1][2] * n.z,
+        c[2][0] * n.x + c[2][1] * n.y + c[2][2] * n.z,
+    );
+
+    if result.len() > 0.0 {
+        result.unit()
+    } else {
+        result
+    }
+}
+
+/// deformed position and normal of a single vertex
+pub fn skin_vertex(vertex: &Vertex, palette: &[Mat4]) -> (Vec3, Vec3) {
+    let skin = skin_matrix(vertex, palette);
+
+    (
+        transform_point(&skin, &vertex.pos),
+        transform_normal(&skin, &vertex.norm),
+    )
+}
+
+/// weighted blend of the joint dual quaternions influencing a vertex
+/// quaternions on the opposite hemisphere of the first influence get flipped so the blend takes the short way
+pub fn skin_dual_quat(vertex: &Vertex, palette: &[DualQuat]) -> DualQuat {
+    let mut skin = DualQuat::IDENTITY * 0.0;
+    let mut first: Option<DualQuat> = None;
+
+    for i in 0..4 {
+        let id = vertex.bone_ids[i];
+        let weight = vertex.weights[i];
+        if id < 0 || weight == 0.0 || id as usize >= palette.len() {
+            continue;
+        }
+
+        let mut dq = palette[id as usize];
+        match first {
+            Some(first) if first.dot(&dq) < 0.0 => dq = -dq,
+            None => first = Some(dq),
+            _ => {}
+        }
+        skin = skin + dq * weight;
+    }
+
+    if first.is_none() {
+        return DualQuat::IDENTITY;
+    }
+
+    skin.unit()
+}
+
+/// same as skin_vertex but with a dual quaternion palette(Object::get_dual_quat_pose)
+pub fn skin_vertex_dual_quat(vertex: &Vertex, palette: &[DualQuat]) -> (Vec3, Vec3) {
+    let skin = skin_dual_quat(vertex, palette);
+
+    let normal = skin.transform_vector(&vertex.norm);
+    let normal = if normal.len() > 0.0 {
+        normal.unit()
+    } else {
+        normal
+    };
+
+    (skin.transform_point(&vertex.pos), normal)
+}
+
+/// morph targets are applied first, same as on the gpu
+pub fn skin_mesh(mesh: &Mesh, palette: &[Mat4]) -> SkinnedMesh {
+    let vertices = mesh.morphed_vertices();
+
+    let mut result = SkinnedMesh {
+        positions: Vec::with_capacity(vertices.len()),
+        normals: Vec::with_capacity(vertices.len()),
+    };
+    vertices.iter().for_each(|vertex| {
+        let (position, normal) = skin_vertex(vertex, palette);
+        result.positions.push(position);
+        result.normals.push(normal);
+    });
+
+    result
+}
+
+pub fn skin_mesh_dual_quat(mesh: &Mesh, palette: &[DualQuat]) -> SkinnedMesh {
+    let vertices = mesh.morphed_vertices();
+
+    let mut result = SkinnedMesh {
+        positions: Vec::with_capacity(vertices.len()),
+        normals: Vec::with_capacity(vertices.len()),
+    };
+    vertices.iter().for_each(|vertex| {
+        let (position, normal) = skin_vertex_dual_quat(vertex, palette);
+        result.positions.push(position);
+        result.normals.push(normal);
+    });
+
+    result
+}
+
+/// one palette for every mesh, Object::get_skinned_meshes handles models with several skins
+pub fn skin_model(model: &Model, palette: &[Mat4]) -> Vec<SkinnedMesh> {
+    model
+        .meshes
+        .iter()
+        .map(|mesh| skin_mesh(mesh, palette))
+        .collect()
+}
+
+pub fn skin_model_dual_quat(model: &Model, palette: &[DualQuat]) -> Vec<SkinnedMesh> {
+    model
+        .meshes
+        .iter()
+        .map(|mesh| skin_mesh_dual_quat(mesh, palette))
+        .collect()
+}
+
+/// copy of a mesh frozen in the current pose, ready to be rendered without skinning or exported
+pub fn posed_mesh(mesh: &Mesh, palette: &[Mat4]) -> Mesh {
+    let skinned = skin_mesh(mesh, palette);
+
+    let mut result = Mesh::default();
+    result.indices = mesh.indices.clone();
+    result.mesh_id = mesh.mesh_id;
+    result.vertices = mesh
+        .morphed_vertices()
+        .iter()
+        .enumerate()
+        .map(|(i, vertex)| Vertex {
+            pos: skinned.positions[i],
+            norm: skinned.normals[i],
+            weights: [0.0; 4],
+            bone_ids: [-1; 4],
+            ..*vertex
+        })
+        .collect();
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::quaternion::Quat;
+    use crate::src::transform::Transform;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).len() < 0.0001
+    }
+
+    fn vertex(pos: Vec3, weights: [f32; 4], bone_ids: [i32; 4]) -> Vertex {
+        Vertex {
+            pos,
+            norm: vec3(0.0, 1.0, 0.0),
+            weights,
+            bone_ids,
+            ..Vertex::DEFAULT
+        }
+    }
+
+    fn moved(x: f32, y: f32, z: f32) -> Mat4 {
+        let mut transform = Transform::DEFAULT;
+        transform.translation = vec3(x, y, z);
+        transform.to_mat()
+    }
+
+    #[test]
+    fn unweighted_vertices_stay_put() {
+        let v = vertex(vec3(1.0, 2.0, 3.0), [0.0; 4], [-1; 4]);
+        let (pos, norm) = skin_vertex(&v, &[moved(5.0, 0.0, 0.0)]);
+
+        assert!(close(pos, v.pos));
+        assert!(close(norm, v.norm));
+    }
+
+    #[test]
+    fn unused_slots_are_skipped() {
+        // -1 and ids past the palette are ignored even with a weight, like the shader does
+        let palette = [moved(2.0, 0.0, 0.0)];
+        let v = vertex(vec3(1.0, 1.0, 1.0), [1.0, 0.25, 0.25, 0.0], [0, -1, 7, -1]);
+        let (pos, _) = skin_vertex(&v, &palette);
+
+        assert!(close(pos, vec3(3.0, 1.0, 1.0)));
+    }
+
+    #[test]
+    fn weights_blend_the_joints() {
+        let palette = [moved(2.0, 0.0, 0.0), moved(0.0, 2.0, 0.0)];
+        let v = vertex(vec3(1.0, 1.0, 1.0), [0.5, 0.5, 0.0, 0.0], [0, 1, -1, -1]);
+        let (pos, _) = skin_vertex(&v, &palette);
+
+        assert!(close(pos, vec3(2.0, 2.0, 1.0)));
+    }
+
+    #[test]
+    fn linear_and_dual_quaternion_agree_on_rigid_joints() {
+        let mut transform = Transform::DEFAULT;
+        transform.translation = vec3(1.0, -2.0, 0.5);
+        transform.orientation = Quat::create(75.0, vec3(0.0, 0.0, 1.0));
+
+        let mats = [transform.to_mat()];
+        let dqs = [DualQuat::from_mat(&mats[0])];
+
+        let v = vertex(vec3(0.5, 1.0, -1.0), [1.0, 0.0, 0.0, 0.0], [0, -1, -1, -1]);
+        let (linear_pos, linear_norm) = skin_vertex(&v, &mats);
+        let (dq_pos, dq_norm) = skin_vertex_dual_quat(&v, &dqs);
+
+        assert!(close(linear_pos, dq_pos));
+        assert!(close(linear_norm, dq_norm));
+    }
+
+    #[test]
+    fn scaled_joints_are_not_rigid() {
+        let mut transform = Transform::DEFAULT;
+        transform.orientation = Quat::create(40.0, vec3(1.0, 0.0, 0.0));
+        transform.translation = vec3(3.0, 0.0, 0.0);
+        assert!(is_rigid(&transform.to_mat()));
+
+        transform.scaling = vec3(1.0, 2.0, 1.0);
+        assert!(!is_rigid(&transform.to_mat()));
+
+        // scale that cancels out between the pose and the inverse bind pose is fine
+        let mut inverse = Transform::DEFAULT;
+        inverse.scaling = vec3(0.5, 0.5, 0.5);
+        transform.scaling = vec3(2.0, 2.0, 2.0);
+        assert!(is_rigid(&(transform.to_mat() * inverse.to_mat())));
+    }
+}
@@ -0,0 +1,287 @@
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// spring bones
+// secondary motion for tails, hair, antennae etc...
+// every joint after the first is a point pulled towards where the animation wants it by a damped spring
+// the simulation runs in world space so moving the object drags the chain behind it
+
+use crate::math::{quaternion::*, vec3::*};
+use crate::src::animation::ik::{self, IKChain};
+use crate::src::animation::pose::Pose;
+use crate::src::skeleton::Skeleton;
+use crate::src::transform::Transform;
+
+/// sphere attached to a joint that the chain can't pass through(head, shoulders...)
+#[derive(Clone, Copy)]
+pub struct SpringCollider {
+    pub joint: usize,
+    /// in the joints local space
+    pub offset: Vec3,
+    /// world units
+    pub radius: f32,
+}
+
+#[derive(Clone)]
+pub struct SpringChain {
+    /// from the joint the chain hangs off to the tip
+    pub joints: Vec<usize>,
+    /// how hard each point is pulled towards its animated position
+    pub stiffness: f32,
+    /// how fast the wobbling dies down
+    pub damping: f32,
+    /// world space acceleration
+    pub gravity: Vec3,
+    pub colliders: Vec<SpringCollider>,
+    pub enabled: bool,
+    /// simulated world space positions, parallel to joints
+    positions: Vec<Vec3>,
+    velocities: Vec<Vec3>,
+}
+
+impl SpringChain {
+    pub fn new(joints: Vec<usize>) -> Self {
+        Self {
+            joints,
+            stiffness: 120.0,
+            damping: 8.0,
+            gravity: vec3(0.0, -9.8, 0.0),
+            colliders: Vec::new(),
+            enabled: true,
+            positions: Vec::new(),
+            velocities: Vec::new(),
+        }
+    }
+
+    /// chain from 'root' down to 'tip'
+    pub fn from_names(skeleton: &Skeleton, root: &str, tip: &str) -> Option<Self> {
+        let chain = IKChain::from_names(skeleton, root, tip)?;
+        Some(Self::new(chain.joints))
+    }
+
+    /// one chain per run of joints with 'keyword' in their name("antenna" finds both antennae)
+    /// a chain starts at a matching joint whose parent doesn't match and follows matching children down
+    pub fn find_all(skeleton: &Skeleton, keyword: &str) -> Vec<Self> {
+        let keyword = keyword.to_lowercase();
+        let parents = &skeleton.rest_pose.parents;
+        let matches = |joint: usize| {
+            skeleton
+                .joint_names
+                .get(joint)
+                .is_some_and(|name| name.to_lowercase().contains(&keyword))
+        };
+
+        let mut chains = Vec::new();
+        for root in 0..parents.len() {
+            let parent = parents[root];
+            if !matches(root) || (parent >= 0 && matches(parent as usize)) {
+                continue;
+            }
+
+            let mut joints = vec![root];
+            let mut joint = root;
+            while let Some(child) =
+                (0..parents.len()).find(|&i| parents[i] == joint as i32 && matches(i))
+            {
+                joints.push(child);
+                joint = child;
+            }
+
+            // a single joint has nothing to swing
+            if joints.len() > 1 {
+                chains.push(Self::new(joints));
+            }
+        }
+
+        chains
+    }
+
+    pub fn set_stiffness(&mut self, stiffness: f32) -> &mut Self {
+        self.stiffness = stiffness;
+        self
+    }
+
+    pub fn set_damping(&mut self, damping: f32) -> &mut Self {
+        self.damping = damping;
+        self
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vec3) -> &mut Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn add_collider(&mut self, joint: usize, offset: Vec3, radius: f32) -> &mut Self {
+        self.colliders.push(SpringCollider {
+            joint,
+            offset,
+            radius,
+        });
+        self
+    }
+
+    /// forget the simulated state, the chain snaps back to the animation on the next update
+    pub fn reset(&mut self) {
+        self.positions.clear();
+        self.velocities.clear();
+    }
+
+    /// model: the objects transform, dt: time since the last update
+    pub fn update(&mut self, pose: &mut Pose, model: &Transform, dt: f32) {
+        let len = self.joints.len();
+        if !self.enabled || len < 2 {
+            return;
+        }
+
+        let world = |pose: &Pose, joint: usize| {
+            model.transform_point(&pose.get_global_tranform(joint).translation)
+        };
+
+        if self.positions.len() != len {
+            self.positions = self.joints.iter().map(|&j| world(pose, j)).collect();
+            self.velocities = vec![Vec3::ZERO; len];
+            return;
+        }
+
+        // big hitches would make the springs explode, so they get split up
+        let steps = (dt / (1.0 / 60.0)).ceil().clamp(1.0, 8.0) as usize;
+        let dt = dt / steps as f32;
+
+        let colliders: Vec<(Vec3, f32)> = self
+            .colliders
+            .iter()
+            .map(|c| {
+                let global = pose.get_global_tranform(c.joint);
+                let center = model.transform_point(&global.transform_point(&c.offset));
+                (center, c.radius)
+            })
+            .collect();
+
+        let to_model = model.inverse();
+        self.positions[0] = world(pose, self.joints[0]);
+
+        for i in 1..len {
+            let parent = self.joints[i - 1];
+            let joint = self.joints[i];
+
+            // the parent may have just been moved by the previous iteration
+            let parent_position = world(pose, parent);
+            let animated = world(pose, joint);
+            let length = (animated - parent_position).len();
+
+            for _ in 0..steps {
+                let accel = (animated - self.positions[i]) * self.stiffness
+                    - self.velocities[i] * self.damping
+                    + self.gravity;
+                self.velocities[i] = self.velocities[i] + accel * dt;
+                self.positions[i] = self.positions[i] + self.velocities[i] * dt;
+            }
+
+            // push out of the colliders then keep the bone its original length
+            let mut position = self.positions[i];
+            for &(center, radius) in colliders.iter() {
+                let offset = position - center;
+                if offset.len() < radius && offset.len() > 0.00001 {
+                    position = center + offset.unit() * radius;
+                }
+            }
+            let dir = position - parent_position;
+            if dir.len() > 0.00001 {
+                position = parent_position + dir.unit() * length;
+            }
+            self.positions[i] = position;
+
+            // turn the parent so its child lands on the simulated point
+            let parent_model = to_model.transform_point(&parent_position);
+            let from = to_model.transform_point(&animated) - parent_model;
+            let to = to_model.transform_point(&position) - parent_model;
+            if from.len() > 0.00001 && to.len() > 0.00001 {
+                ik::rotate_world(pose, parent, Quat::from_to(&from, &to));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a tail sticking out sideways from the body, one unit per bone
+    fn skeleton() -> Skeleton {
+        let mut skeleton = Skeleton::new();
+        skeleton.joint_names = vec![
+            "body".into(),
+            "tail1".into(),
+            "tail2".into(),
+            "tail3".into(),
+        ];
+        skeleton.rest_pose.resize(4);
+        for joint in 1..4 {
+            skeleton.rest_pose.parents[joint] = joint as i32 - 1;
+            skeleton.rest_pose.joints[joint].translation = vec3(1.0, 0.0, 0.0);
+        }
+
+        skeleton
+    }
+
+    /// runs the chain on the rest pose for 'frames' updates, returns the last pose
+    fn simulate(chain: &mut SpringChain, skeleton: &Skeleton, frames: usize) -> Pose {
+        let mut pose = skeleton.rest_pose.clone();
+        for _ in 0..frames {
+            pose = skeleton.rest_pose.clone();
+            chain.update(&mut pose, &Transform::DEFAULT, 1.0 / 60.0);
+        }
+
+        pose
+    }
+
+    #[test]
+    fn chain_settles_at_its_rest_length() {
+        let skeleton = skeleton();
+        let mut chain = SpringChain::from_names(&skeleton, "tail1", "tail3").unwrap();
+        let pose = simulate(&mut chain, &skeleton, 600);
+        let settled = chain.positions.clone();
+        simulate(&mut chain, &skeleton, 1);
+
+        // the bones keep their length and the tail hangs still below where it's animated
+        for (i, &joint) in chain.joints.iter().enumerate().skip(1) {
+            let parent = pose.get_global_tranform(chain.joints[i - 1]).translation;
+            let position = pose.get_global_tranform(joint).translation;
+            assert!(((position - parent).len() - 1.0).abs() < 0.001);
+            assert!((position - settled[i]).len() < 0.001);
+            assert!((chain.positions[i] - settled[i]).len() < 0.001);
+        }
+        assert!(settled[2].y < -0.01);
+    }
+
+    #[test]
+    fn without_gravity_the_chain_follows_the_animation() {
+        let skeleton = skeleton();
+        let mut chain = SpringChain::from_names(&skeleton, "tail1", "tail3").unwrap();
+        chain.set_gravity(Vec3::ZERO);
+        let pose = simulate(&mut chain, &skeleton, 120);
+
+        let tip = pose.get_global_tranform(3).translation;
+        assert!((tip - vec3(3.0, 0.0, 0.0)).len() < 0.001);
+    }
+
+    #[test]
+    fn find_all_splits_chains_by_keyword() {
+        let mut skeleton = Skeleton::new();
+        skeleton.joint_names = [
+            "head",
+            "Antenna.L1",
+            "Antenna.L2",
+            "antenna.r1",
+            "antenna.r2",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+        skeleton.rest_pose.resize(5);
+        skeleton.rest_pose.parents = vec![-1, 0, 1, 0, 3];
+
+        let chains = SpringChain::find_all(&skeleton, "antenna");
+        let joints: Vec<Vec<usize>> = chains.into_iter().map(|chain| chain.joints).collect();
+        assert_eq!(joints, [vec![1, 2], vec![3, 4]]);
+    }
+}
@@ -0,0 +1,324 @@
+use crate::src::animation::clip::Clip;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// data driven animation graph
+// gameplay code sets parameters and the machine decides which clip plays
+// the actual fading is left to the cross fade controller
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Parameter {
+    Float(f32),
+    Bool(bool),
+    /// stays set until a transition consumes it
+    Trigger(bool),
+}
+
+/// a single requirement that has to be met before a transition can fire
+#[derive(Clone, Debug)]
+pub enum Condition {
+    Greater(String, f32),
+    Less(String, f32),
+    IsTrue(String),
+    IsFalse(String),
+    Triggered(String),
+}
+
+#[derive(Clone)]
+pub struct Transition {
+    /// None means the transition can be taken from any state
+    pub from: Option<usize>,
+    pub to: usize,
+    pub conditions: Vec<Condition>,
+    /// normalized time(0.0 - 1.0) of the current clip that has to pass before leaving
+    pub exit_time: Option<f32>,
+    /// cross fade duration in seconds
+    pub duration: f32,
+}
+
+#[derive(Clone)]
+pub struct State {
+    pub name: String,
+    /// index into the objects animation list
+    pub clip: usize,
+}
+
+#[derive(Clone)]
+pub struct StateMachine {
+    states: Vec<State>,
+    transitions: Vec<Transition>,
+    parameters: HashMap<String, Parameter>,
+    current: usize,
+    /// time spent in the current state
+    state_time: f32,
+}
+
+impl Transition {
+    pub fn when_greater(&mut self, name: &str, value: f32) -> &mut Self {
+        self.conditions
+            .push(Condition::Greater(String::from(name), value));
+        self
+    }
+
+    pub fn when_less(&mut self, name: &str, value: f32) -> &mut Self {
+        self.conditions
+            .push(Condition::Less(String::from(name), value));
+        self
+    }
+
+    pub fn when_true(&mut self, name: &str) -> &mut Self {
+        self.conditions.push(Condition::IsTrue(String::from(name)));
+        self
+    }
+
+    pub fn when_false(&mut self, name: &str) -> &mut Self {
+        self.conditions.push(Condition::IsFalse(String::from(name)));
+        self
+    }
+
+    pub fn when_triggered(&mut self, name: &str) -> &mut Self {
+        self.conditions
+            .push(Condition::Triggered(String::from(name)));
+        self
+    }
+
+    pub fn with_exit_time(&mut self, exit_time: f32) -> &mut Self {
+        self.exit_time = Some(exit_time);
+        self
+    }
+}
+
+impl StateMachine {
+    pub fn new() -> Self {
+        Self {
+            states: Vec::new(),
+            transitions: Vec::new(),
+            parameters: HashMap::new(),
+            current: 0,
+            state_time: 0.0,
+        }
+    }
+
+    /// returns the index of the new state
+    /// the first state added is the entry state
+    pub fn add_state(&mut self, name: &str, clip: usize) -> usize {
+        self.states.push(State {
+            name: String::from(name),
+            clip,
+        });
+
+        self.states.len() - 1
+    }
+
+    /// from: None allows the transition from any state
+    pub fn add_transition(
+        &mut self,
+        from: Option<usize>,
+        to: usize,
+        duration: f32,
+    ) -> &mut Transition {
+        self.transitions.push(Transition {
+            from,
+            to,
+            conditions: Vec::new(),
+            exit_time: None,
+            duration,
+        });
+
+        self.transitions.last_mut().unwrap()
+    }
+
+    pub fn get_state(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|state| state.name == name)
+    }
+
+    pub fn get_current_state(&self) -> &State {
+        &self.states[self.current]
+    }
+
+    pub fn get_current_clip(&self) -> usize {
+        self.states[self.current].clip
+    }
+
+    /// jump straight to a state without checking any transitions
+    pub fn set_state(&mut self, state: usize) {
+        self.current = state;
+        self.state_time = 0.0;
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.parameters
+            .insert(String::from(name), Parameter::Float(value));
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.parameters
+            .insert(String::from(name), Parameter::Bool(value));
+    }
+
+    pub fn set_trigger(&mut self, name: &str) {
+        self.parameters
+            .insert(String::from(name), Parameter::Trigger(true));
+    }
+
+    pub fn reset_trigger(&mut self, name: &str) {
+        self.parameters
+            .insert(String::from(name), Parameter::Trigger(false));
+    }
+
+    pub fn get_parameter(&self, name: &str) -> Option<Parameter> {
+        self.parameters.get(name).copied()
+    }
+
+    /// advances the machine and returns the (clip, fade duration) to fade to if a transition fired
+    pub fn update(&mut self, clips: &[Rc<Clip>], dt: f32) -> Option<(usize, f32)> {
+        if self.states.is_empty() {
+            return None;
+        }
+
+        self.state_time += dt;
+
+        let duration = clips[self.get_current_clip()].get_duration();
+        let normalized = if duration > 0.0 {
+            self.state_time / duration
+        } else {
+            1.0
+        };
+
+        let fired = self.transitions.iter().position(|transition| {
+            if let Some(from) = transition.from {
+                if from != self.current {
+                    return false;
+                }
+            } else if transition.to == self.current {
+                // any state transitions shouldn't keep restarting their own target
+                return false;
+            }
+
+            if let Some(exit_time) = transition.exit_time {
+                if normalized < exit_time {
+                    return false;
+                }
+            }
+
+            transition
+                .conditions
+                .iter()
+                .all(|condition| self.check(condition))
+        });
+
+        let transition = &self.transitions[fired?];
+        let to = transition.to;
+        let duration = transition.duration;
+
+        // triggers are consumed by the transition that used them
+        let consumed: Vec<String> = transition
+            .conditions
+            .iter()
+            .filter_map(|condition| match condition {
+                Condition::Triggered(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+        consumed.iter().for_each(|name| self.reset_trigger(name));
+
+        self.set_state(to);
+
+        Some((self.get_current_clip(), duration))
+    }
+
+    fn check(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Greater(name, value) => match self.parameters.get(name) {
+                Some(Parameter::Float(v)) => v > value,
+                _ => false,
+            },
+            Condition::Less(name, value) => match self.parameters.get(name) {
+                Some(Parameter::Float(v)) => v < value,
+                _ => false,
+            },
+            Condition::IsTrue(name) => match self.parameters.get(name) {
+                Some(Parameter::Bool(v)) => *v,
+                _ => false,
+            },
+            // unset bools count as false
+            Condition::IsFalse(name) => {
+                !matches!(self.parameters.get(name), Some(Parameter::Bool(true)))
+            }
+            Condition::Triggered(name) => match self.parameters.get(name) {
+                Some(Parameter::Trigger(v)) => *v,
+                _ => false,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::src::animation::frame::VectorFrame;
+    use crate::src::animation::track_transform::TransformTrack;
+
+    /// two clips, two seconds long each
+    fn clips() -> Vec<Rc<Clip>> {
+        let mut track = TransformTrack::new();
+        for time in [0.0, 2.0] {
+            let mut frame = VectorFrame::new();
+            frame.time = time;
+            track.position.frames.push(frame);
+        }
+
+        let mut clip = Clip::new();
+        clip.tracks.push(track);
+        clip.re_calculate_duration();
+        vec![Rc::new(clip.clone()), Rc::new(clip)]
+    }
+
+    #[test]
+    fn exit_time_waits_for_the_clip() {
+        let clips = clips();
+        let mut machine = StateMachine::new();
+        let attack = machine.add_state("attack", 0);
+        let idle = machine.add_state("idle", 1);
+        machine
+            .add_transition(Some(attack), idle, 0.25)
+            .with_exit_time(0.75);
+
+        // 1.4 of 2.0 seconds is 0.7 of the clip
+        assert_eq!(machine.update(&clips, 1.4), None);
+        assert_eq!(machine.get_current_state().name, "attack");
+        assert_eq!(machine.update(&clips, 0.1), Some((1, 0.25)));
+        assert_eq!(machine.get_current_state().name, "idle");
+    }
+
+    #[test]
+    fn triggers_are_consumed() {
+        let clips = clips();
+        let mut machine = StateMachine::new();
+        let idle = machine.add_state("idle", 0);
+        let jump = machine.add_state("jump", 1);
+        machine
+            .add_transition(None, jump, 0.1)
+            .when_triggered("jump");
+        machine
+            .add_transition(Some(jump), idle, 0.1)
+            .when_less("height", 0.5);
+
+        machine.set_float("height", 1.0);
+        assert_eq!(machine.update(&clips, 0.1), None);
+        machine.set_trigger("jump");
+        assert_eq!(machine.update(&clips, 0.1), Some((1, 0.1)));
+        assert_eq!(
+            machine.get_parameter("jump"),
+            Some(Parameter::Trigger(false))
+        );
+
+        // landing goes back to idle, the used up trigger doesn't jump again
+        machine.set_float("height", 0.0);
+        assert_eq!(machine.update(&clips, 0.1), Some((0, 0.1)));
+        assert_eq!(machine.update(&clips, 0.1), None);
+    }
+}
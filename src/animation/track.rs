@@ -17,9 +17,11 @@ use crate::src::animation::frame;
 pub struct Track<const N: usize> {
     pub frames: Vec<frame::Frame<N>>,
     pub interpolation: curves::Interpolation,
+    /// frame index for evenly spaced sample times, empty until 'update_index_lookup_table' is called
+    /// needs to be rebuilt whenever the frames change
+    sampled_frames: Vec<usize>,
 }
 
-#[allow(unused)]
 pub type ScalarTrack = Track<1>;
 pub type VectorTrack = Track<3>;
 pub type QuatTrack = Track<4>;
@@ -29,9 +31,64 @@ impl<const N: usize> Track<N> {
         Self {
             frames: Vec::new(),
             interpolation: curves::Interpolation::Cubic,
+            sampled_frames: Vec::new(),
         }
     }
 
+    /// cache which frame is active at evenly spaced times so finding a frame doesn't need a search
+    /// samples_per_second: how many table entries to create per second of animation
+    pub fn update_index_lookup_table(&mut self, samples_per_second: f32) {
+        self.sampled_frames.clear();
+
+        let len = self.frames.len();
+        if len <= 1 {
+            return;
+        }
+
+        let start_time = self.get_start_time();
+        let duration = self.get_end_time() - start_time;
+        if duration <= 0.0 {
+            return;
+        }
+
+        let samples = ((duration * samples_per_second) as usize).max(2);
+        self.sampled_frames.resize(samples, 0);
+
+        let mut frame = 0;
+        for i in 0..samples {
+            let time = start_time + duration * (i as f32 / (samples - 1) as f32);
+            // times only increase so the search can carry on from the last frame found
+            while frame + 1 < len - 1 && time >= self.frames[frame + 1].time {
+                frame += 1;
+            }
+            self.sampled_frames[i] = frame;
+        }
+    }
+
+    pub fn has_index_lookup_table(&self) -> bool {
+        !self.sampled_frames.is_empty()
+    }
+
+    /// constant time version of the frame search
+    fn lookup_frame_index(&self, time: f32) -> usize {
+        let len = self.frames.len();
+        let start_time = self.get_start_time();
+        let duration = self.get_end_time() - start_time;
+        let samples = self.sampled_frames.len();
+
+        let t = (time - start_time) / duration;
+        let sample = ((t * (samples - 1) as f32) as usize).min(samples - 1);
+
+        // the table holds the frame at the start of each sample,
+        // the actual time can be a few frames further along if the keys are very dense
+        let mut frame = self.sampled_frames[sample];
+        while frame + 1 < len - 1 && time >= self.frames[frame + 1].time {
+            frame += 1;
+        }
+
+        frame
+    }
+
     pub fn get_start_time(&self) -> f32 {
         self.frames[0].time
     }
@@ -147,6 +204,10 @@ impl<const N: usize> Track<N> {
             }
         }
 
+        if self.has_index_lookup_table() {
+            return Ok(self.lookup_frame_index(time));
+        }
+
         for i in (0..len).rev() {
             if time >= self.frames[i].time {
                 return Ok(i);
@@ -295,3 +356,45 @@ impl Interpolate<Quat> for Quat {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// unevenly spaced keys, some closer together than the table entries
+    fn track() -> ScalarTrack {
+        let mut track = ScalarTrack::new();
+        track.interpolation = curves::Interpolation::Linear;
+        let times = [0.5, 0.52, 0.55, 0.9, 1.6, 1.61, 1.62, 2.4, 3.0];
+        for (i, time) in times.into_iter().enumerate() {
+            let mut frame = frame::ScalarFrame::new();
+            frame.time = time;
+            frame.m_value = [(i * i) as f32];
+            track.frames.push(frame);
+        }
+
+        track
+    }
+
+    #[test]
+    fn lookup_table_matches_the_search() {
+        let search = track();
+        let mut lookup = track();
+        lookup.update_index_lookup_table(4.0);
+        assert!(lookup.has_index_lookup_table());
+
+        for i in 0..=400 {
+            let time = i as f32 * 0.01;
+            for looping in [false, true] {
+                assert_eq!(
+                    lookup.frame_index(time, looping),
+                    search.frame_index(time, looping),
+                    "time {time} looping {looping}"
+                );
+                let a: f32 = lookup.sample(time, looping);
+                let b: f32 = search.sample(time, looping);
+                assert_eq!(a, b);
+            }
+        }
+    }
+}
@@ -37,6 +37,13 @@ impl TransformTrack {
         }
     }
 
+    /// see Track::update_index_lookup_table
+    pub fn update_index_lookup_table(&mut self, samples_per_second: f32) {
+        self.position.update_index_lookup_table(samples_per_second);
+        self.rotation.update_index_lookup_table(samples_per_second);
+        self.scaling.update_index_lookup_table(samples_per_second);
+    }
+
     pub fn get_start_time(&self) -> f32 {
         let mut result = 0.0;
         let mut is_set = false;
@@ -91,7 +98,7 @@ impl TransformTrack {
         result
     }
 
-    pub fn sample(&mut self, reference: &Transform, time: f32, looping: bool) -> Transform {
+    pub fn sample(&self, reference: &Transform, time: f32, looping: bool) -> Transform {
         let mut result = *reference;
 
         if self.position.frames.len() > 1 {
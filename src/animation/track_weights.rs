@@ -0,0 +1,80 @@
+use crate::src::animation::track::ScalarTrack;
+
+use std::collections::HashMap;
+
+/// animated morph target(blend shape) weights for a single mesh
+/// one scalar track per morph target
+#[derive(Clone)]
+pub struct WeightTrack {
+    /// mesh id the weights are applied to
+    pub id: u32,
+    pub weights: Vec<ScalarTrack>,
+}
+
+impl WeightTrack {
+    pub fn new() -> Self {
+        Self {
+            id: 0,
+            weights: Vec::new(),
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.weights.iter().any(|track| track.frames.len() > 1)
+    }
+
+    pub fn get_start_time(&self) -> f32 {
+        let mut result = 0.0;
+        let mut is_set = false;
+
+        for track in self.weights.iter() {
+            if track.frames.len() > 1 {
+                let start = track.get_start_time();
+                if start < result || !is_set {
+                    result = start;
+                    is_set = true;
+                }
+            }
+        }
+
+        result
+    }
+
+    pub fn get_end_time(&self) -> f32 {
+        let mut result = 0.0;
+        let mut is_set = false;
+
+        for track in self.weights.iter() {
+            if track.frames.len() > 1 {
+                let end = track.get_end_time();
+                if end > result || !is_set {
+                    result = end;
+                    is_set = true;
+                }
+            }
+        }
+
+        result
+    }
+
+    /// writes the sampled weights into 'out' under this tracks mesh id
+    pub fn sample(&self, out: &mut HashMap<u32, Vec<f32>>, time: f32, looping: bool) {
+        let weights = out.entry(self.id).or_default();
+        weights.resize(self.weights.len(), 0.0);
+
+        for (i, track) in self.weights.iter().enumerate() {
+            weights[i] = match track.frames.len() {
+                0 => weights[i],
+                1 => track.frames[0].m_value[0],
+                _ => track.sample::<f32>(time, looping),
+            };
+        }
+    }
+
+    /// see Track::update_index_lookup_table
+    pub fn update_index_lookup_table(&mut self, samples_per_second: f32) {
+        self.weights.iter_mut().for_each(|track| {
+            track.update_index_lookup_table(samples_per_second);
+        });
+    }
+}
@@ -0,0 +1,408 @@
+use crate::math::{quaternion::Quat, vec3::Vec3};
+use crate::src::animation::animator::{self, PlayMode};
+use crate::src::animation::curves::{Bezier, Interpolation};
+use crate::src::animation::frame::ScalarFrame;
+use crate::src::animation::track::ScalarTrack;
+use crate::src::camera::Camera;
+use crate::src::lights::PointLight;
+use crate::src::shapes::shape::Shape;
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// tweens
+// a single float going from one value to another over time, shaped by an easing curve or a scalar track
+// it can write straight into a field(light intensity, pattern shade, camera fov...) or just be read
+// the easing formulas are the usual ones from easings.net
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+    /// overshoots backwards before heading to the end
+    BackIn,
+    /// overshoots the end and settles back
+    BackOut,
+    BackInOut,
+}
+
+impl Easing {
+    /// t from 0.0 to 1.0, the result starts at 0.0 and ends at 1.0 but elastic and back leave that range in between
+    pub fn ease(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        // back overshoot
+        let c1 = 1.70158;
+        let c2 = c1 * 1.525;
+        let c3 = c1 + 1.0;
+        // elastic period
+        let c4 = (2.0 * PI) / 3.0;
+        let c5 = (2.0 * PI) / 4.5;
+
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::ElasticIn => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    -(2.0f32.powf(10.0 * t - 10.0)) * ((10.0 * t - 10.75) * c4).sin()
+                }
+            }
+            Easing::ElasticOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    2.0f32.powf(-10.0 * t) * ((10.0 * t - 0.75) * c4).sin() + 1.0
+                }
+            }
+            Easing::ElasticInOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else if t < 0.5 {
+                    -(2.0f32.powf(20.0 * t - 10.0) * ((20.0 * t - 11.125) * c5).sin()) / 2.0
+                } else {
+                    (2.0f32.powf(-20.0 * t + 10.0) * ((20.0 * t - 11.125) * c5).sin()) / 2.0 + 1.0
+                }
+            }
+            Easing::BounceIn => 1.0 - bounce_out(1.0 - t),
+            Easing::BounceOut => bounce_out(t),
+            Easing::BounceInOut => {
+                if t < 0.5 {
+                    (1.0 - bounce_out(1.0 - 2.0 * t)) / 2.0
+                } else {
+                    (1.0 + bounce_out(2.0 * t - 1.0)) / 2.0
+                }
+            }
+            Easing::BackIn => c3 * t * t * t - c1 * t * t,
+            Easing::BackOut => 1.0 + c3 * (t - 1.0).powi(3) + c1 * (t - 1.0).powi(2),
+            Easing::BackInOut => {
+                if t < 0.5 {
+                    ((2.0 * t).powi(2) * ((c2 + 1.0) * 2.0 * t - c2)) / 2.0
+                } else {
+                    ((2.0 * t - 2.0).powi(2) * ((c2 + 1.0) * (t * 2.0 - 2.0) + c2) + 2.0) / 2.0
+                }
+            }
+        }
+    }
+}
+
+fn bounce_out(t: f32) -> f32 {
+    let n1 = 7.5625;
+    let d1 = 2.75;
+
+    if t < 1.0 / d1 {
+        n1 * t * t
+    } else if t < 2.0 / d1 {
+        let t = t - 1.5 / d1;
+        n1 * t * t + 0.75
+    } else if t < 2.5 / d1 {
+        let t = t - 2.25 / d1;
+        n1 * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / d1;
+        n1 * t * t + 0.984375
+    }
+}
+
+/// bakes an easing curve into a scalar track, handy for editing it further or saving it with a clip
+pub fn ease_track(
+    from: f32,
+    to: f32,
+    duration: f32,
+    easing: Easing,
+    samples_per_second: f32,
+) -> ScalarTrack {
+    let mut track = ScalarTrack::new();
+    track.interpolation = Interpolation::Linear;
+
+    let samples = (duration * samples_per_second).ceil().max(1.0) as usize;
+    for i in 0..=samples {
+        let t = i as f32 / samples as f32;
+
+        let mut frame = ScalarFrame::new();
+        frame.time = duration * t;
+        frame.m_value = [from + (to - from) * easing.ease(t)];
+        track.frames.push(frame);
+    }
+
+    track
+}
+
+/// where the value comes from
+#[derive(Clone)]
+pub enum TweenCurve {
+    Ease {
+        from: f32,
+        to: f32,
+        easing: Easing,
+    },
+    /// sampled in seconds, the tweens duration should match the tracks
+    Track(ScalarTrack),
+}
+
+/// what the value gets written into
+#[derive(Clone)]
+pub enum TweenTarget {
+    /// nothing, read the value with Tween::get_value
+    None,
+    /// index into the point light list
+    LightIntensity(usize),
+    /// shade of the checkered or striped pattern of a shape
+    PatternShade(String),
+    /// vertical field of view in degrees
+    CameraFov,
+    /// moves a shape along the curve, the tweens value is the position on the curve(0.0 to 1.0)
+    ShapePath(String, Bezier<Vec3>),
+    /// turns a shape along the curve, same as ShapePath
+    ShapeRotation(String, Bezier<Quat>),
+}
+
+#[derive(Clone)]
+pub struct Tween {
+    pub curve: TweenCurve,
+    pub target: TweenTarget,
+    pub duration: f32,
+    /// seconds to wait before starting
+    pub delay: f32,
+    pub mode: PlayMode,
+    pub enabled: bool,
+    time: f32,
+    /// seconds of the delay still left
+    waiting: f32,
+    /// ping pong direction, 1.0 forwards -1.0 backwards
+    direction: f32,
+    finished: bool,
+}
+
+impl Tween {
+    pub fn new(curve: TweenCurve, target: TweenTarget, duration: f32) -> Self {
+        Self {
+            curve,
+            target,
+            duration,
+            delay: 0.0,
+            mode: PlayMode::Once,
+            enabled: true,
+            time: 0.0,
+            waiting: 0.0,
+            direction: 1.0,
+            finished: false,
+        }
+    }
+
+    /// the usual from -> to tween
+    pub fn ease(from: f32, to: f32, duration: f32, easing: Easing, target: TweenTarget) -> Self {
+        Self::new(TweenCurve::Ease { from, to, easing }, target, duration)
+    }
+
+    pub fn set_delay(&mut self, delay: f32) -> &mut Self {
+        self.delay = delay;
+        self.restart();
+        self
+    }
+
+    pub fn set_mode(&mut self, mode: PlayMode) -> &mut Self {
+        self.mode = mode;
+        self.restart();
+        self
+    }
+
+    /// back to the start of the delay, reverse starts at the end
+    pub fn restart(&mut self) {
+        self.time = match self.mode {
+            PlayMode::Reverse => self.duration,
+            _ => 0.0,
+        };
+        self.waiting = self.delay;
+        self.direction = 1.0;
+        self.finished = false;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// true during the delay, nothing gets applied until it runs out
+    pub fn is_waiting(&self) -> bool {
+        self.waiting > 0.0
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        if !self.enabled || self.finished {
+            return;
+        }
+
+        // whatever is left of the update after the delay runs out goes into the tween
+        let mut dt = dt;
+        if self.waiting > 0.0 {
+            self.waiting -= dt;
+            if self.waiting > 0.0 {
+                return;
+            }
+            dt = -self.waiting;
+            self.waiting = 0.0;
+        }
+
+        let step = animator::step_time(
+            self.mode,
+            self.time,
+            self.direction,
+            dt,
+            0.0,
+            self.duration.max(0.00001),
+        );
+        self.time = step.time;
+        self.direction = step.direction;
+        self.finished = step.finished;
+    }
+
+    /// 0.0 to 1.0 through the tween
+    pub fn get_progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+
+        (self.time / self.duration).clamp(0.0, 1.0)
+    }
+
+    pub fn get_value(&self) -> f32 {
+        match &self.curve {
+            TweenCurve::Ease { from, to, easing } => {
+                from + (to - from) * easing.ease(self.get_progress())
+            }
+            TweenCurve::Track(track) => {
+                match track.frames.len() {
+                    0 => return 0.0,
+                    1 => return track.frames[0].m_value[0],
+                    _ => {}
+                }
+                track.sample::<f32>(self.get_progress() * self.duration, false)
+            }
+        }
+    }
+
+    /// write the current value into the target, missing targets are ignored
+    pub fn apply(
+        &self,
+        shapes: &mut HashMap<String, Shape>,
+        lights: &mut [PointLight],
+        camera: &mut Camera,
+    ) {
+        if !self.enabled || self.is_waiting() {
+            return;
+        }
+
+        let value = self.get_value();
+        match &self.target {
+            TweenTarget::None => {}
+            TweenTarget::LightIntensity(index) => {
+                if let Some(light) = lights.get_mut(*index) {
+                    light.intensity = value;
+                }
+            }
+            TweenTarget::PatternShade(name) => {
+                if let Some(shape) = shapes.get_mut(name) {
+                    shape.set_pattern_shade(value);
+                }
+            }
+            TweenTarget::CameraFov => camera.fov = value,
+            TweenTarget::ShapePath(name, path) => {
+                if let Some(shape) = shapes.get_mut(name) {
+                    shape.transform.translation = path.interpolate(value);
+                }
+            }
+            TweenTarget::ShapeRotation(name, path) => {
+                if let Some(shape) = shapes.get_mut(name) {
+                    shape.transform.orientation = path.interpolate(value);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linear(duration: f32) -> Tween {
+        Tween::ease(0.0, 10.0, duration, Easing::Linear, TweenTarget::None)
+    }
+
+    #[test]
+    fn easing_ends() {
+        for easing in [
+            Easing::Linear,
+            Easing::QuadInOut,
+            Easing::CubicOut,
+            Easing::ElasticIn,
+            Easing::BounceInOut,
+            Easing::BackOut,
+        ] {
+            assert!(easing.ease(0.0).abs() < 0.0001);
+            assert!((easing.ease(1.0) - 1.0).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn delay_is_waited_out_first() {
+        let mut tween = linear(1.0);
+        tween.set_delay(1.0);
+
+        tween.update(0.5);
+        assert!(tween.is_waiting());
+        assert_eq!(tween.get_progress(), 0.0);
+
+        // the part of the update past the delay goes into the tween
+        tween.update(0.75);
+        assert!(!tween.is_waiting());
+        assert!((tween.get_value() - 2.5).abs() < 0.0001);
+
+        tween.update(1.0);
+        assert!(tween.is_finished());
+        assert_eq!(tween.get_value(), 10.0);
+    }
+
+    #[test]
+    fn play_modes_match_the_animator() {
+        let mut tween = linear(1.0);
+        tween.set_mode(PlayMode::Reverse);
+        tween.update(0.25);
+        assert!((tween.get_progress() - 0.75).abs() < 0.0001);
+
+        tween.set_mode(PlayMode::PingPong);
+        tween.update(1.25);
+        assert!((tween.get_progress() - 0.75).abs() < 0.0001);
+        tween.update(0.5);
+        assert!((tween.get_progress() - 0.25).abs() < 0.0001);
+    }
+}
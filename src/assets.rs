@@ -1,3 +1,4 @@
+use crate::src::foreign::gltf::Scene;
 use crate::src::{lights::PointLight, object::Object, shaders::Program};
 use std::collections::HashMap;
 
@@ -27,6 +28,27 @@ impl Assets {
             self.objects.insert(String::from(n), o);
         }
     }
+    /// one object per node with a mesh, named after the node
+    /// names already taken get the node index added so nothing is dropped
+    pub fn add_scene(&mut self, scene: Scene) {
+        scene
+            .nodes
+            .into_iter()
+            .filter(|node| !node.meshes.is_empty())
+            .for_each(|node| {
+                let mut object = Object::new();
+                object.transform = node.global;
+                object.model.meshes = node.meshes;
+                object.model.prepere_render_resources();
+
+                let mut name = node.name;
+                if self.objects.contains_key(&name) {
+                    name = format!("{name} {}", node.node);
+                }
+                self.add_object(&name, object);
+            });
+    }
+
     pub fn get_object(&mut self, n: &str) -> &mut Object {
         self.objects.get_mut(&String::from(n)).unwrap()
     }
@@ -1,4 +1,4 @@
-use crate::math::{mat4::*, misc::*, vec3::*};
+use crate::math::{mat4::*, misc::*, quaternion::Quat, vec3::*};
 
 pub enum Direction {
     None,
@@ -16,6 +16,8 @@ pub struct Camera {
     pub pos: Vec3,
     pub velocity: f32,
     pub dir: Direction,
+    /// vertical field of view in degrees
+    pub fov: f32,
 }
 
 impl Camera {
@@ -28,6 +30,7 @@ impl Camera {
             pitch: 0.0,
             yaw: radians(90.0),
             dir: Direction::None,
+            fov: 45.0,
         }
     }
 
@@ -40,6 +43,7 @@ impl Camera {
             pitch: 0.0,
             yaw: radians(90.0),
             dir: Direction::None,
+            fov: 45.0,
         }
     }
 
@@ -65,6 +69,15 @@ impl Camera {
         self.front = new_front.unit();
     }
 
+    /// point the camera the way the rotated +z axis faces, yaw and pitch follow so the mouse carries on from there
+    pub fn set_orientation(&mut self, orientation: Quat) {
+        let front = (orientation * vec3(0.0, 0.0, 1.0)).unit();
+
+        self.pitch = clamp(front.y.asin(), radians(-89.0), radians(89.0));
+        self.yaw = front.z.atan2(front.x);
+        self.front = front;
+    }
+
     fn back(&mut self) {
         self.pos = self.pos - self.front * self.velocity;
     }
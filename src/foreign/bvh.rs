@@ -0,0 +1,384 @@
+use crate::math::quaternion::Quat;
+use crate::math::vec3::*;
+use crate::src::animation::clip::Clip;
+use crate::src::animation::curves::Interpolation;
+use crate::src::animation::frame::{QuaternionFrame, VectorFrame};
+use crate::src::animation::pose::Pose;
+use crate::src::animation::track_transform::TransformTrack;
+use crate::src::transform::Transform;
+
+use std::path::Path;
+
+//_______________________________________________________________________________________________
+//_______________________________________________________________________________________________
+// bvh loader
+// motion capture files, a joint hierarchy with offsets followed by one line of channel values per frame
+// rotations are euler angles in degrees applied in the order the channels are listed
+// end sites only mark where the last bone ends so they don't become joints
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Channel {
+    XPosition,
+    YPosition,
+    ZPosition,
+    XRotation,
+    YRotation,
+    ZRotation,
+}
+
+struct BvhJoint {
+    name: String,
+    parent: i32,
+    offset: Vec3,
+    channels: Vec<Channel>,
+}
+
+pub struct BvhFile {
+    joints: Vec<BvhJoint>,
+    /// every channel value of every joint, one list per frame
+    frames: Vec<Vec<f32>>,
+    frame_time: f32,
+    name: String,
+}
+
+impl BvhFile {
+    pub fn new(path: &Path) -> Result<BvhFile, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("couldn't load bvh file: {e}"))?;
+        let mut file = BvhFile::parse(&text)?;
+        file.name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or(String::from("None"));
+
+        Ok(file)
+    }
+
+    pub fn parse(text: &str) -> Result<BvhFile, String> {
+        // the hierarchy doesn't care about lines, the motion has one frame per line
+        let (hierarchy, motion) = text
+            .split_once("MOTION")
+            .ok_or(String::from("missing MOTION"))?;
+        let joints = parse_hierarchy(hierarchy)?;
+
+        let mut lines = motion
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty());
+        let frame_count: usize = lines
+            .next()
+            .and_then(|line| line.strip_prefix("Frames:"))
+            .ok_or(String::from("missing Frames:"))?
+            .trim()
+            .parse()
+            .map_err(|_| String::from("invalid frame count"))?;
+        let frame_time = lines
+            .next()
+            .and_then(|line| line.strip_prefix("Frame Time:"))
+            .ok_or(String::from("missing Frame Time:"))?;
+        let frame_time = parse_float(frame_time.trim())?;
+
+        let channel_count: usize = joints.iter().map(|j| j.channels.len()).sum();
+        let mut frames = Vec::with_capacity(frame_count);
+        for f in 0..frame_count {
+            let line = lines
+                .next()
+                .ok_or(format!("expected {frame_count} frames, found {f}"))?;
+            let values = line
+                .split_whitespace()
+                .map(parse_float)
+                .collect::<Result<Vec<f32>, String>>()?;
+            if values.len() != channel_count {
+                return Err(format!(
+                    "frame {f} has {} values, expected {channel_count}",
+                    values.len()
+                ));
+            }
+            frames.push(values);
+        }
+
+        Ok(BvhFile {
+            joints,
+            frames,
+            frame_time,
+            name: String::from("None"),
+        })
+    }
+
+    pub fn extract_joint_names(&self) -> Vec<String> {
+        self.joints.iter().map(|joint| joint.name.clone()).collect()
+    }
+
+    /// the offsets without any rotation, bvh files don't store a separate bind pose
+    pub fn extract_rest_pose(&self) -> Pose {
+        let mut pose = Pose::new();
+        pose.resize(self.joints.len());
+
+        self.joints.iter().enumerate().for_each(|(i, joint)| {
+            pose.joints[i] = Transform {
+                translation: joint.offset,
+                orientation: Quat::ZERO,
+                scaling: Vec3::ONE,
+            };
+            pose.parents[i] = joint.parent;
+        });
+
+        pose
+    }
+
+    pub fn extract_clip(&self) -> Clip {
+        let mut clip = Clip::new();
+        clip.name = self.name.clone();
+
+        let mut first_channel = 0;
+        self.joints.iter().enumerate().for_each(|(i, joint)| {
+            let mut track = TransformTrack::new();
+            track.id = i as u32;
+            track.position.interpolation = Interpolation::Linear;
+            track.rotation.interpolation = Interpolation::Linear;
+
+            let has_position = joint.channels.iter().any(|c| is_position(*c));
+            let has_rotation = joint.channels.iter().any(|c| !is_position(*c));
+
+            for (f, values) in self.frames.iter().enumerate() {
+                let time = f as f32 * self.frame_time;
+                let values = &values[first_channel..first_channel + joint.channels.len()];
+                let (position, rotation) = joint_transform(joint, values);
+
+                if has_position {
+                    let mut frame = VectorFrame::new();
+                    frame.time = time;
+                    frame.m_value = position.to_array();
+                    track.position.frames.push(frame);
+                }
+                if has_rotation {
+                    let mut frame = QuaternionFrame::new();
+                    frame.time = time;
+                    frame.m_value = rotation.to_array();
+                    track.rotation.frames.push(frame);
+                }
+            }
+
+            first_channel += joint.channels.len();
+            if track.is_valid() {
+                clip.tracks.push(track);
+            }
+        });
+        clip.re_calculate_duration();
+
+        clip
+    }
+}
+
+/// joints of the HIERARCHY section, parents always come before their children
+fn parse_hierarchy(text: &str) -> Result<Vec<BvhJoint>, String> {
+    let mut tokens = text.split_whitespace();
+    let mut joints: Vec<BvhJoint> = Vec::new();
+    // joints whose braces are still open, -1 marks an end site
+    let mut stack: Vec<i32> = Vec::new();
+
+    let mut next = |what: &str| -> Result<&str, String> {
+        tokens
+            .next()
+            .ok_or(format!("unexpected end of hierarchy, expected {what}"))
+    };
+
+    if next("HIERARCHY")? != "HIERARCHY" {
+        return Err(String::from("missing HIERARCHY"));
+    }
+
+    loop {
+        let token = match next("a joint") {
+            Ok(token) => token,
+            // every brace closed, the hierarchy is done
+            Err(_) if stack.is_empty() && !joints.is_empty() => break,
+            Err(e) => return Err(e),
+        };
+        match token {
+            "ROOT" | "JOINT" => {
+                let name = next("joint name")?.to_string();
+                let parent = stack.iter().rev().find(|&&j| j >= 0).copied().unwrap_or(-1);
+                joints.push(BvhJoint {
+                    name,
+                    parent,
+                    offset: Vec3::ZERO,
+                    channels: Vec::new(),
+                });
+                stack.push(joints.len() as i32 - 1);
+                if next("{")? != "{" {
+                    return Err(String::from("expected { after joint name"));
+                }
+            }
+            "End" => {
+                next("Site")?;
+                stack.push(-1);
+                if next("{")? != "{" {
+                    return Err(String::from("expected { after End Site"));
+                }
+            }
+            "OFFSET" => {
+                let mut offset = [0.0; 3];
+                for value in offset.iter_mut() {
+                    *value = parse_float(next("offset")?)?;
+                }
+                if let Some(&joint) = stack.last() {
+                    if joint >= 0 {
+                        joints[joint as usize].offset = Vec3::from(&offset);
+                    }
+                }
+            }
+            "CHANNELS" => {
+                let count: usize = next("channel count")?
+                    .parse()
+                    .map_err(|_| String::from("invalid channel count"))?;
+                let mut channels = Vec::new();
+                for _ in 0..count {
+                    channels.push(parse_channel(next("channel")?)?);
+                }
+                match stack.last() {
+                    Some(&joint) if joint >= 0 => joints[joint as usize].channels = channels,
+                    _ => return Err(String::from("CHANNELS outside of a joint")),
+                }
+            }
+            "}" => {
+                if stack.pop().is_none() {
+                    return Err(String::from("unmatched }"));
+                }
+            }
+            other => return Err(format!("unexpected token {other} in hierarchy")),
+        }
+    }
+
+    Ok(joints)
+}
+
+/// local position and rotation of a joint from its channel values
+/// position channels replace the offset, rotations are applied in the listed order
+fn joint_transform(joint: &BvhJoint, values: &[f32]) -> (Vec3, Quat) {
+    let mut position = joint.offset;
+    let mut rotation = Quat::ZERO;
+
+    joint
+        .channels
+        .iter()
+        .zip(values)
+        .for_each(|(channel, &value)| match channel {
+            Channel::XPosition => position.x = value,
+            Channel::YPosition => position.y = value,
+            Channel::ZPosition => position.z = value,
+            Channel::XRotation => rotation = rotation * Quat::create(value, vec3(1.0, 0.0, 0.0)),
+            Channel::YRotation => rotation = rotation * Quat::create(value, vec3(0.0, 1.0, 0.0)),
+            Channel::ZRotation => rotation = rotation * Quat::create(value, vec3(0.0, 0.0, 1.0)),
+        });
+
+    (position, rotation.unit())
+}
+
+fn is_position(channel: Channel) -> bool {
+    matches!(
+        channel,
+        Channel::XPosition | Channel::YPosition | Channel::ZPosition
+    )
+}
+
+fn parse_channel(token: &str) -> Result<Channel, String> {
+    match token.to_lowercase().as_str() {
+        "xposition" => Ok(Channel::XPosition),
+        "yposition" => Ok(Channel::YPosition),
+        "zposition" => Ok(Channel::ZPosition),
+        "xrotation" => Ok(Channel::XRotation),
+        "yrotation" => Ok(Channel::YRotation),
+        "zrotation" => Ok(Channel::ZRotation),
+        _ => Err(format!("unknown channel {token}")),
+    }
+}
+
+fn parse_float(token: &str) -> Result<f32, String> {
+    token.parse().map_err(|_| format!("invalid number {token}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINIMAL: &str = "HIERARCHY
+ROOT Hips
+{
+    OFFSET 0.0 1.0 0.0
+    CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+    JOINT Spine
+    {
+        OFFSET 0.0 0.5 0.0
+        CHANNELS 3 Zrotation Xrotation Yrotation
+        End Site
+        {
+            OFFSET 0.0 0.5 0.0
+        }
+    }
+}
+MOTION
+Frames: 2
+Frame Time: 0.5
+0.0 1.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+2.0 1.0 0.0 0.0 0.0 0.0 90.0 0.0 0.0
+";
+
+    #[test]
+    fn minimal_file() {
+        let file = BvhFile::parse(MINIMAL).unwrap();
+        assert_eq!(file.extract_joint_names(), ["Hips", "Spine"]);
+
+        let rest = file.extract_rest_pose();
+        assert_eq!(rest.parents, [-1, 0]);
+        assert!((rest.joints[1].translation - vec3(0.0, 0.5, 0.0)).len() < 0.0001);
+
+        let clip = file.extract_clip();
+        assert_eq!(clip.tracks.len(), 2);
+        assert!((clip.get_duration() - 0.5).abs() < 0.0001);
+
+        // half way the hips moved 1 unit along x and the spine turned an eighth around z
+        let mut pose = rest.clone();
+        clip.sample(&mut pose, 0.25);
+        assert!((pose.joints[0].translation - vec3(1.0, 1.0, 0.0)).len() < 0.0001);
+        let tip = pose.joints[1].orientation * vec3(0.0, 1.0, 0.0);
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((tip - vec3(-half, half, 0.0)).len() < 0.0001);
+    }
+
+    #[test]
+    fn malformed_channel_counts_are_rejected() {
+        // says 3 channels but lists 2, the next token isn't a channel
+        let text = MINIMAL.replace("CHANNELS 3 Zrotation Xrotation", "CHANNELS 3 Zrotation");
+        assert!(BvhFile::parse(&text).is_err());
+
+        let text = MINIMAL.replace("CHANNELS 3", "CHANNELS three");
+        assert_eq!(
+            BvhFile::parse(&text).err().unwrap(),
+            "invalid channel count"
+        );
+    }
+
+    #[test]
+    fn truncated_frames_are_rejected() {
+        // the last frame lost a value
+        let text = MINIMAL.trim_end().trim_end_matches("0.0").to_string();
+        assert_eq!(
+            BvhFile::parse(&text).err().unwrap(),
+            "frame 1 has 8 values, expected 9"
+        );
+
+        // a whole frame is missing
+        let text = MINIMAL.replace("Frames: 2", "Frames: 3");
+        assert_eq!(
+            BvhFile::parse(&text).err().unwrap(),
+            "expected 3 frames, found 2"
+        );
+    }
+
+    #[test]
+    fn unclosed_joints_are_rejected() {
+        let text = MINIMAL.replacen("}\n}\nMOTION", "}\nMOTION", 1);
+        assert!(BvhFile::parse(&text).is_err());
+        assert!(BvhFile::parse("HIERARCHY\nROOT Hips\n{\n").is_err());
+    }
+}
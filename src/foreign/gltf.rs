@@ -2,12 +2,15 @@ use crate::math::mat4::{transpose, Mat4};
 use crate::math::{quaternion::*, vec2::*, vec3::*};
 use crate::src::animation::pose::Pose;
 use crate::src::model::*;
+use crate::src::skeleton::Skin;
 use crate::src::transform::Transform;
 
 use crate::src::animation::clip::Clip;
 use crate::src::animation::curves::Interpolation;
-use crate::src::animation::frame::{QuaternionFrame, VectorFrame};
+use crate::src::animation::frame::{QuaternionFrame, ScalarFrame, VectorFrame};
+use crate::src::animation::track::ScalarTrack;
 use crate::src::animation::track_transform::TransformTrack;
+use crate::src::animation::track_weights::WeightTrack;
 
 use std::path::Path;
 //_______________________________________________________________________________________________
@@ -17,6 +20,25 @@ use std::path::Path;
 // still a work in progress
 extern crate gltf;
 
+/// one node of a scene with its meshes already placed in the world
+#[derive(Clone)]
+pub struct SceneNode {
+    pub name: String,
+    /// index of the node in the file
+    pub node: usize,
+    /// every parent transform applied, the nodes are placed on their own without a hierarchy
+    pub global: Transform,
+    /// copies of the nodes mesh primitives, empty for nodes without a mesh
+    pub meshes: Vec<Mesh>,
+}
+
+#[derive(Clone)]
+pub struct Scene {
+    pub name: String,
+    /// parents always come before their children
+    pub nodes: Vec<SceneNode>,
+}
+
 /// 0: documnet, 1: buffers, 2: images
 /// planning on implimenting an image loader soon an materials
 #[allow(unused)]
@@ -80,85 +102,143 @@ impl GltfFile {
             skin.joints().for_each(|joint| {
                 joints.push(joint.index() as i32);
             });
-            skins[0] = joints;
+            skins[skin.index()] = joints;
         });
 
+        let mesh_skins = self.extract_mesh_skins();
+        let no_skin = Vec::new();
+
+        // a mesh skinned by several skins gets its primitives once per skin
+        // the joint ids of each copy are remapped through its own skin
         document.meshes().for_each(|mesh| {
-            let primitives = mesh.primitives();
-            //assuming it only contains one skin
-            let ids = &skins[0];
-
-            primitives.for_each(|primitive| {
-                //prepare for next batch of data
-                let mut mesh = Mesh::default();
-
-                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
-                //primitive.material().index();
-                // extract positions
-                if let Some(positions) = reader.read_positions() {
-                    positions.for_each(|pos| {
-                        mesh.vertices.push(Vertex {
-                            pos: Vec3::from(&pos),
-                            ..Vertex::DEFAULT
+            mesh_skins[mesh.index()].iter().for_each(|&skin| {
+                let mesh_id = mesh.index() as u32;
+                let default_weights = mesh.weights().unwrap_or(&[]).to_vec();
+                let primitives = mesh.primitives();
+                // joint indices in the file are relative to the skin, turn them into node indices
+                let ids = skin.map(|skin| &skins[skin]).unwrap_or(&no_skin);
+
+                primitives.for_each(|primitive| {
+                    //prepare for next batch of data
+                    let mut mesh = Mesh::default();
+
+                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                    //primitive.material().index();
+                    // extract positions
+                    if let Some(positions) = reader.read_positions() {
+                        positions.for_each(|pos| {
+                            mesh.vertices.push(Vertex {
+                                pos: Vec3::from(&pos),
+                                ..Vertex::DEFAULT
+                            });
                         });
-                    });
-                };
-
-                //extract normals
-                if let Some(normals) = reader.read_normals() {
-                    normals.enumerate().for_each(|(i, norm)| {
-                        mesh.vertices[i].norm = Vec3::from(&norm);
-                    });
-                }
-
-                //extract colors
-                if let Some(colors) = reader.read_colors(0) {
-                    colors.into_rgb_f32().enumerate().for_each(|(i, color)| {
-                        mesh.vertices[i].col = Vec3::from(&color);
-                    });
-                }
-                //extract texture coordinates
-                if let Some(texels) = reader.read_tex_coords(0) {
-                    texels.into_f32().enumerate().for_each(|(i, texel)| {
-                        mesh.vertices[i].tex = Vec2::from(&texel);
-                    });
-                }
+                    };
 
-                //extract weights
-                if let Some(weights) = reader.read_weights(0) {
-                    weights.into_f32().enumerate().for_each(|(i, weight)| {
-                        mesh.vertices[i].weights = weight;
-                    });
-                }
+                    //extract normals
+                    if let Some(normals) = reader.read_normals() {
+                        normals.enumerate().for_each(|(i, norm)| {
+                            mesh.vertices[i].norm = Vec3::from(&norm);
+                        });
+                    }
 
-                //extract bone ids
-                if let Some(boneids) = reader.read_joints(0) {
-                    boneids.into_u16().enumerate().for_each(|(i, batch)| {
-                        if ids.len() > 0 {
-                            mesh.vertices[i].bone_ids = [
-                                ids[batch[0] as usize],
-                                ids[batch[1] as usize],
-                                ids[batch[2] as usize],
-                                ids[batch[3] as usize],
-                            ];
-                        } else {
-                            mesh.vertices[i].bone_ids = batch.map(|id| id as i32);
-                        }
-                    });
-                }
+                    //extract colors
+                    if let Some(colors) = reader.read_colors(0) {
+                        colors.into_rgb_f32().enumerate().for_each(|(i, color)| {
+                            mesh.vertices[i].col = Vec3::from(&color);
+                        });
+                    }
+                    //extract texture coordinates
+                    if let Some(texels) = reader.read_tex_coords(0) {
+                        texels.into_f32().enumerate().for_each(|(i, texel)| {
+                            mesh.vertices[i].tex = Vec2::from(&texel);
+                        });
+                    }
 
-                //extract indices
-                if let Some(indices) = reader.read_indices() {
-                    mesh.indices = indices.into_u32().collect();
-                }
+                    //extract weights
+                    if let Some(weights) = reader.read_weights(0) {
+                        weights.into_f32().enumerate().for_each(|(i, weight)| {
+                            mesh.vertices[i].weights = weight;
+                        });
+                    }
+
+                    //extract bone ids
+                    if let Some(boneids) = reader.read_joints(0) {
+                        boneids.into_u16().enumerate().for_each(|(i, batch)| {
+                            if ids.len() > 0 {
+                                mesh.vertices[i].bone_ids = [
+                                    ids[batch[0] as usize],
+                                    ids[batch[1] as usize],
+                                    ids[batch[2] as usize],
+                                    ids[batch[3] as usize],
+                                ];
+                            } else {
+                                mesh.vertices[i].bone_ids = batch.map(|id| id as i32);
+                            }
+                        });
+                    }
+
+                    //extract indices
+                    if let Some(indices) = reader.read_indices() {
+                        mesh.indices = indices.into_u32().collect();
+                    }
+
+                    // extract blend shapes
+                    mesh.mesh_id = mesh_id;
+                    mesh.skin = skin;
+                    reader
+                        .read_morph_targets()
+                        .for_each(|(positions, normals, _)| {
+                            let mut target = MorphTarget {
+                                positions: Vec::new(),
+                                normals: Vec::new(),
+                            };
+                            if let Some(positions) = positions {
+                                target.positions = positions.map(|p| Vec3::from(&p)).collect();
+                            }
+                            if let Some(normals) = normals {
+                                target.normals = normals.map(|n| Vec3::from(&n)).collect();
+                            }
+                            mesh.morph_targets.push(target);
+                        });
+                    mesh.morph_weights = default_weights.clone();
+                    mesh.morph_weights.resize(mesh.morph_targets.len(), 0.0);
 
-                meshes.push(mesh);
+                    meshes.push(mesh);
+                });
             });
         });
 
         meshes
     }
 
+    /// skins of every mesh, taken from the nodes the mesh hangs off in the order they are found
+    /// meshes that aren't on a skinned node use the only skin if the file has exactly one, otherwise None
+    fn extract_mesh_skins(&self) -> Vec<Vec<Option<usize>>> {
+        let document = &self.0;
+
+        let mut mesh_skins = vec![Vec::new(); document.meshes().count()];
+        document.nodes().for_each(|node| {
+            if let (Some(mesh), Some(skin)) = (node.mesh(), node.skin()) {
+                let skins = &mut mesh_skins[mesh.index()];
+                if !skins.contains(&Some(skin.index())) {
+                    skins.push(Some(skin.index()));
+                }
+            }
+        });
+
+        let fallback = if document.skins().count() == 1 {
+            Some(0)
+        } else {
+            None
+        };
+        mesh_skins
+            .iter_mut()
+            .filter(|skins| skins.is_empty())
+            .for_each(|skins| skins.push(fallback));
+
+        mesh_skins
+    }
+
     /* pub fn extract_materials(&self) {
         let document = &self.0;
 
@@ -213,31 +293,140 @@ impl GltfFile {
         pose
     }
 
+    /// every skins inverse bind matrices merged into one list, used for meshes without a skin(Mesh::skin is None)
+    /// every mesh of a skinned file carries its skin and gets its matrices from 'extract_skins' instead
+    /// a joint bound differently by two skins keeps the first one found and gets reported
     pub fn extract_inverse_bind_mats(&self) -> Vec<Option<Mat4>> {
         let document = &self.0;
-        let buffers = &self.1;
 
         let mut inv_poses: Vec<Option<Mat4>> = Vec::new();
         inv_poses.resize(document.nodes().count(), None);
 
-        // assumes theres only one skin
-        // need to fix this
+        self.extract_skins().iter().for_each(|skin| {
+            skin.joints
+                .iter()
+                .zip(skin.inverse_bind_pose.iter())
+                .for_each(|(&joint, mat)| {
+                    let kept = *inv_poses[joint].get_or_insert(*mat);
+                    if kept != *mat {
+                        println!(
+                            "joint {joint} is bound differently by skin {}, the first binding is kept",
+                            skin.name
+                        );
+                    }
+                });
+        });
+
+        inv_poses
+    }
+
+    pub fn extract_skins(&self) -> Vec<Skin> {
+        let document = &self.0;
+        let buffers = &self.1;
+
+        let mut skins = Vec::new();
         document.skins().for_each(|skin| {
             let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
 
-            let mut inv_bind_mats = Vec::new();
+            let mut result = Skin {
+                name: skin.name().unwrap_or("None").to_string(),
+                joints: skin.joints().map(|joint| joint.index()).collect(),
+                inverse_bind_pose: Vec::new(),
+            };
+
+            // no matrices means they are all identity
             if let Some(inverse_bind_mats) = reader.read_inverse_bind_matrices() {
-                inv_bind_mats = inverse_bind_mats.collect();
+                result.inverse_bind_pose = inverse_bind_mats
+                    .map(|mat| transpose(&Mat4::from(&mat)))
+                    .collect();
             }
+            result
+                .inverse_bind_pose
+                .resize(result.joints.len(), Mat4::IDENTITY);
 
-            for (i, joint) in skin.joints().enumerate() {
-                let inv_mat = &Mat4::from(&inv_bind_mats[i]);
+            skins.push(result);
+        });
 
-                inv_poses[joint.index()] = Some(transpose(inv_mat));
-            }
+        skins
+    }
+
+    //_______________________________________________________________________________________________
+    //_______________________________________________________________________________________________
+    // scene loading
+    // walks the node tree of a scene so meshes end up where they were placed in the editor
+    // a mesh used by several nodes gets a copy per node
+
+    /// scene 'index' of the file, only its own nodes get walked
+    pub fn extract_scene(&self, index: usize) -> Option<Scene> {
+        let scene = self.0.scenes().nth(index)?;
+        let meshes = self.extract_meshes();
+
+        let mut result = Scene {
+            name: scene
+                .name()
+                .map(|name| name.to_string())
+                .unwrap_or(format!("scene {}", scene.index())),
+            nodes: Vec::new(),
+        };
+        scene.nodes().for_each(|node| {
+            Self::extract_scene_node(&node, &Transform::DEFAULT, &meshes, &mut result.nodes);
         });
 
-        inv_poses
+        Some(result)
+    }
+
+    /// the scene the file wants shown first, falls back to the first one
+    pub fn extract_default_scene(&self) -> Option<Scene> {
+        let index = self
+            .0
+            .default_scene()
+            .map(|scene| scene.index())
+            .unwrap_or(0);
+
+        self.extract_scene(index)
+    }
+
+    fn extract_scene_node(
+        node: &gltf::Node,
+        parent_transform: &Transform,
+        meshes: &[Mesh],
+        out: &mut Vec<SceneNode>,
+    ) {
+        let global = parent_transform.combine(&Self::get_local_transform(node));
+
+        let mut result = SceneNode {
+            name: node
+                .name()
+                .map(|name| name.to_string())
+                .unwrap_or(format!("node {}", node.index())),
+            node: node.index(),
+            global,
+            meshes: Vec::new(),
+        };
+        if let Some(mesh) = node.mesh() {
+            // a mesh comes out once per skin, the node takes the copy bound with its own skin
+            // nodes without a skin take the first copy, the bone ids aren't used for them
+            let primitives = meshes
+                .iter()
+                .filter(|primitive| primitive.mesh_id == mesh.index() as u32);
+            let skin = match node.skin() {
+                Some(skin) => Some(skin.index()),
+                None => primitives
+                    .clone()
+                    .next()
+                    .and_then(|primitive| primitive.skin),
+            };
+            result.meshes = primitives
+                .filter(|primitive| primitive.skin == skin)
+                .cloned()
+                .collect();
+        }
+
+        out.push(result);
+
+        node.children().for_each(|child| {
+            Self::extract_scene_node(&child, &global, meshes, out);
+        });
     }
 
     //_______________________________________________________________________________________________
@@ -322,6 +511,75 @@ impl GltfFile {
         track_transform
     }
 
+    // morph target weights are stored as one flat list, every keyframe holds a weight per target
+    fn extract_weights(&self, channel: &gltf::animation::Channel) -> Option<WeightTrack> {
+        let buffers = &self.1;
+        let sampler = &channel.sampler();
+        let mesh = channel.target().node().mesh()?;
+
+        let mut interpolation = Interpolation::Constant;
+        if sampler.interpolation() == gltf::animation::Interpolation::Linear {
+            interpolation = Interpolation::Linear;
+        } else if sampler.interpolation() == gltf::animation::Interpolation::CubicSpline {
+            interpolation = Interpolation::Cubic;
+        }
+
+        let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+
+        let times: Vec<f32> = match reader.read_inputs()? {
+            gltf::accessor::Iter::Standard(times) => times.collect(),
+            gltf::accessor::Iter::Sparse(_) => {
+                println!("sparce key frames not supported");
+                return None;
+            }
+        };
+
+        let values: Vec<f32> = match reader.read_outputs()? {
+            gltf::animation::util::ReadOutputs::MorphTargetWeights(weights) => {
+                weights.into_f32().collect()
+            }
+            _ => return None,
+        };
+
+        if times.is_empty() {
+            return None;
+        }
+
+        // cubic splines store (in tangent, value, out tangent) for every keyframe
+        let stride = if interpolation == Interpolation::Cubic {
+            3
+        } else {
+            1
+        };
+        let count = values.len() / (times.len() * stride);
+
+        let mut track = WeightTrack::new();
+        track.id = mesh.index() as u32;
+
+        for target in 0..count {
+            let mut weights = ScalarTrack::new();
+            weights.interpolation = interpolation;
+            weights.frames.resize(times.len(), ScalarFrame::new());
+
+            for (i, time) in times.iter().enumerate() {
+                let key = i * count * stride;
+                let frame = &mut weights.frames[i];
+                frame.time = *time;
+                if stride == 3 {
+                    frame.m_in[0] = values[key + target];
+                    frame.m_value[0] = values[key + count + target];
+                    frame.m_out[0] = values[key + 2 * count + target];
+                } else {
+                    frame.m_value[0] = values[key + target];
+                }
+            }
+
+            track.weights.push(weights);
+        }
+
+        Some(track)
+    }
+
     pub fn extract_animations(&self) -> Vec<Clip> {
         let document = &self.0;
 
@@ -330,7 +588,13 @@ impl GltfFile {
             let mut clip = Clip::new();
             clip.name = animation.name().unwrap().to_string();
             animation.channels().for_each(|channel| {
-                clip.tracks.push(self.extract_animation(&channel));
+                if channel.target().property() == gltf::animation::Property::MorphTargetWeights {
+                    if let Some(track) = self.extract_weights(&channel) {
+                        clip.weight_tracks.push(track);
+                    }
+                } else {
+                    clip.tracks.push(self.extract_animation(&channel));
+                }
             });
             clip.re_calculate_duration();
             clips.push(clip);
@@ -339,3 +603,173 @@ impl GltfFile {
         clips
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// wraps a gltf json and its binary buffer into a glb
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut bin = bin.to_vec();
+        bin.resize(bin.len().next_multiple_of(4), 0);
+
+        let mut result = Vec::new();
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        result.extend_from_slice(b"glTF");
+        result.extend_from_slice(&2u32.to_le_bytes());
+        result.extend_from_slice(&(length as u32).to_le_bytes());
+        result.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        result.extend_from_slice(b"JSON");
+        result.extend_from_slice(&json);
+        result.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        result.extend_from_slice(b"BIN\0");
+        result.extend_from_slice(&bin);
+
+        result
+    }
+
+    /// one single vertex mesh bound to joint 0 of its skin, used by two nodes with different skins
+    /// skin 0 is joint "a"(node 1), skin 1 is joint "b"(node 2)
+    fn two_skins_one_mesh() -> GltfFile {
+        let mut bin = Vec::new();
+        [0.0f32, 1.0, 0.0]
+            .iter()
+            .for_each(|f| bin.extend_from_slice(&f.to_le_bytes()));
+        [0u16, 0, 0, 0]
+            .iter()
+            .for_each(|i| bin.extend_from_slice(&i.to_le_bytes()));
+        [1.0f32, 0.0, 0.0, 0.0]
+            .iter()
+            .for_each(|f| bin.extend_from_slice(&f.to_le_bytes()));
+
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "scene": 0,
+            "scenes": [{"nodes": [0, 3, 4]}],
+            "nodes": [
+                {"name": "root", "children": [1, 2]},
+                {"name": "a", "translation": [1, 0, 0]},
+                {"name": "b", "translation": [-1, 0, 0]},
+                {"name": "left", "mesh": 0, "skin": 0, "translation": [0, 0, 2]},
+                {"name": "right", "mesh": 0, "skin": 1}
+            ],
+            "skins": [{"name": "first", "joints": [1]}, {"name": "second", "joints": [2]}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0, "JOINTS_0": 1, "WEIGHTS_0": 2}}]}],
+            "buffers": [{"byteLength": 36}],
+            "bufferViews": [
+                {"buffer": 0, "byteOffset": 0, "byteLength": 12},
+                {"buffer": 0, "byteOffset": 12, "byteLength": 8},
+                {"buffer": 0, "byteOffset": 20, "byteLength": 16}
+            ],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3", "min": [0, 1, 0], "max": [0, 1, 0]},
+                {"bufferView": 1, "componentType": 5123, "count": 1, "type": "VEC4"},
+                {"bufferView": 2, "componentType": 5126, "count": 1, "type": "VEC4"}
+            ]
+        }"#;
+
+        let (document, buffers, images) = gltf::import_slice(glb(json, &bin)).unwrap();
+        GltfFile(document, buffers, images)
+    }
+
+    #[test]
+    fn mesh_comes_out_once_per_skin() {
+        let file = two_skins_one_mesh();
+        let meshes = file.extract_meshes();
+
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(meshes[0].skin, Some(0));
+        assert_eq!(meshes[0].vertices[0].bone_ids[0], 1);
+        assert_eq!(meshes[1].skin, Some(1));
+        assert_eq!(meshes[1].vertices[0].bone_ids[0], 2);
+    }
+
+    #[test]
+    fn scene_nodes_take_the_copy_of_their_skin() {
+        let file = two_skins_one_mesh();
+        let scene = file.extract_default_scene().unwrap();
+
+        let node = |name: &str| scene.nodes.iter().find(|node| node.name == name).unwrap();
+        let left = node("left");
+        let right = node("right");
+        assert_eq!(left.meshes.len(), 1);
+        assert_eq!(left.meshes[0].vertices[0].bone_ids[0], 1);
+        assert_eq!(right.meshes.len(), 1);
+        assert_eq!(right.meshes[0].vertices[0].bone_ids[0], 2);
+        assert!(node("root").meshes.is_empty());
+    }
+
+    #[test]
+    fn missing_inverse_bind_matrices_are_identity() {
+        let file = two_skins_one_mesh();
+        let skins = file.extract_skins();
+
+        assert_eq!(skins.len(), 2);
+        assert_eq!(skins[1].joints, vec![2]);
+        assert!(skins[1].inverse_bind_pose[0] == Mat4::IDENTITY);
+
+        let merged = file.extract_inverse_bind_mats();
+        assert!(merged[1].is_some() && merged[2].is_some());
+        assert!(merged[0].is_none());
+    }
+
+    /// a scene file without any buffers, gltf json can be imported as is
+    fn scenes(json: &str) -> GltfFile {
+        let (document, buffers, images) = gltf::import_slice(json.as_bytes()).unwrap();
+        GltfFile(document, buffers, images)
+    }
+
+    #[test]
+    fn default_scene_is_the_one_the_file_asks_for() {
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "scene": 1,
+            "scenes": [{"name": "menu", "nodes": [0]}, {"name": "level", "nodes": [1]}],
+            "nodes": [{"name": "title"}, {"name": "floor"}]
+        }"#;
+        let scene = scenes(json).extract_default_scene().unwrap();
+
+        assert_eq!(scene.name, "level");
+        assert_eq!(scene.nodes.len(), 1);
+        assert_eq!(scene.nodes[0].name, "floor");
+
+        // without a default the first scene is shown
+        let scene = scenes(&json.replace(r#""scene": 1,"#, ""))
+            .extract_default_scene()
+            .unwrap();
+        assert_eq!(scene.name, "menu");
+    }
+
+    #[test]
+    fn children_are_placed_by_their_parents() {
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "scenes": [{"nodes": [0]}],
+            "nodes": [
+                {"name": "house", "translation": [0, 2, 0], "scale": [2, 2, 2], "children": [1]},
+                {"translation": [1, 0, 0], "children": [2]},
+                {"name": "lamp", "translation": [0, 1, 0]}
+            ]
+        }"#;
+        let scene = scenes(json).extract_default_scene().unwrap();
+
+        // parents come before their children
+        let names: Vec<&str> = scene.nodes.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, ["house", "node 1", "lamp"]);
+        assert_eq!(scene.name, "scene 0");
+
+        let lamp = &scene.nodes[2];
+        assert_eq!(lamp.node, 2);
+        assert!((lamp.global.translation - vec3(2.0, 4.0, 0.0)).len() < 0.0001);
+        assert!((lamp.global.scaling - vec3(2.0, 2.0, 2.0)).len() < 0.0001);
+        assert!(scene.nodes.iter().all(|node| node.meshes.is_empty()));
+    }
+
+    #[test]
+    fn missing_scenes_are_none() {
+        let json = r#"{"asset": {"version": "2.0"}, "nodes": [{"name": "lost"}]}"#;
+        assert!(scenes(json).extract_default_scene().is_none());
+    }
+}
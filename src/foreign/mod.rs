@@ -1,2 +1,3 @@
+pub mod bvh;
 pub mod dae;
 pub mod gltf;
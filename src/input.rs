@@ -1,6 +1,7 @@
 extern crate gl;
 use crate::src;
 use crate::src::camera::Direction;
+use crate::src::skeleton_debug::SkeletonDebug;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 
@@ -65,3 +66,19 @@ pub fn mouse_input(event: &Event, cam: &mut src::camera::Camera) {
         _ => {}
     }
 }
+
+/// B toggles the skeleton overlay, N the rest pose next to it, L prints the joints
+pub fn debug_input(event: &Event, debug: &mut SkeletonDebug) {
+    if let Event::KeyDown {
+        keycode: Some(key), ..
+    } = event
+    {
+        match *key {
+            Keycode::B => debug.enabled = !debug.enabled,
+            Keycode::N => debug.show_rest_pose = !debug.show_rest_pose,
+            Keycode::L => debug.print_labels = true,
+
+            _ => {}
+        }
+    }
+}
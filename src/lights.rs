@@ -6,6 +6,8 @@ use crate::src::shadows;
 pub struct PointLight {
     pub pos: Vec3,
     pub col: Vec3,
+    /// multiplies the color
+    pub intensity: f32,
 }
 // only directional light shadow support at the moment
 // might add point light shadows in the future who knows ¯\_(ツ)_/¯
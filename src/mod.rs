@@ -13,6 +13,7 @@ pub mod shaders;
 pub mod shadows;
 pub mod shapes;
 pub mod skeleton;
+pub mod skeleton_debug;
 pub mod timer;
 pub mod transform;
 pub mod world;
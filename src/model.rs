@@ -1,6 +1,7 @@
 use crate::gl;
 
 use crate::math::{vec2::*, vec3::*};
+use std::collections::HashMap;
 use std::mem::offset_of;
 use std::os::raw::c_void;
 
@@ -27,11 +28,27 @@ impl Vertex {
     };
 }
 
+/// per vertex offsets for a blend shape, either list can be empty
+#[derive(Clone)]
+pub struct MorphTarget {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+}
+
 #[derive(Clone)]
 pub struct Mesh {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
 
+    /// index of the mesh this came from in the source file
+    /// primitives of the same mesh share it, morph weight tracks target it
+    pub mesh_id: u32,
+    pub morph_targets: Vec<MorphTarget>,
+    /// weights currently applied to the vertex buffer
+    pub morph_weights: Vec<f32>,
+    /// which of the skeletons skins the bone ids are bound with, None uses the skeletons own inverse bind pose
+    pub skin: Option<usize>,
+
     vao: u32,
     vbo: u32,
     ebo: u32,
@@ -47,6 +64,10 @@ impl Mesh {
         Self {
             vertices: Vec::new(),
             indices: Vec::new(),
+            mesh_id: 0,
+            morph_targets: Vec::new(),
+            morph_weights: Vec::new(),
+            skin: None,
             vao: 0,
             vbo: 0,
             ebo: 0,
@@ -63,12 +84,20 @@ impl Mesh {
 
             let vert_size = std::mem::size_of::<Vertex>();
 
+            // morphed meshes get re-uploaded whenever their weights change
+            let usage = if self.morph_targets.is_empty() {
+                gl::STATIC_DRAW
+            } else {
+                gl::DYNAMIC_DRAW
+            };
+            let vertices = self.morphed_vertices();
+
             gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
             gl::BufferData(
                 gl::ARRAY_BUFFER,
-                (self.vertices.len() * vert_size) as isize,
-                self.vertices.as_ptr() as *const c_void,
-                gl::STATIC_DRAW,
+                (vertices.len() * vert_size) as isize,
+                vertices.as_ptr() as *const c_void,
+                usage,
             );
 
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
@@ -148,6 +177,66 @@ impl Mesh {
         }
     }
 
+    /// base vertices with the current morph weights applied
+    pub fn morphed_vertices(&self) -> Vec<Vertex> {
+        let mut vertices = self.vertices.clone();
+
+        for (i, target) in self.morph_targets.iter().enumerate() {
+            let weight = *self.morph_weights.get(i).unwrap_or(&0.0);
+            if weight == 0.0 {
+                continue;
+            }
+
+            // a target with more deltas than the mesh has vertices only moves the ones that exist
+            vertices
+                .iter_mut()
+                .zip(target.positions.iter())
+                .for_each(|(vertex, delta)| vertex.pos = vertex.pos + *delta * weight);
+            vertices
+                .iter_mut()
+                .zip(target.normals.iter())
+                .for_each(|(vertex, delta)| vertex.norm = vertex.norm + *delta * weight);
+        }
+
+        if self.morph_targets.iter().any(|t| !t.normals.is_empty()) {
+            vertices.iter_mut().for_each(|v| {
+                if v.norm.len() > 0.0 {
+                    v.norm = v.norm.unit();
+                }
+            });
+        }
+
+        vertices
+    }
+
+    /// change the blend shape weights and update the vertex buffer if anything changed
+    /// missing weights count as 0.0 and extra ones are dropped, returns false when nothing changed
+    pub fn set_morph_weights(&mut self, weights: &[f32]) -> bool {
+        let mut weights = weights.to_vec();
+        weights.resize(self.morph_targets.len(), 0.0);
+        if self.morph_weights == weights {
+            return false;
+        }
+        self.morph_weights = weights;
+
+        // not uploaded yet, create() will use the new weights
+        if self.vbo == 0 {
+            return true;
+        }
+
+        let vertices = self.morphed_vertices();
+        unsafe {
+            gl::NamedBufferSubData(
+                self.vbo,
+                0,
+                (vertices.len() * std::mem::size_of::<Vertex>()) as isize,
+                vertices.as_ptr() as *const c_void,
+            );
+        }
+
+        true
+    }
+
     pub fn render(&mut self) {
         if self.indices.len() != 0 {
             unsafe {
@@ -171,6 +260,10 @@ impl Mesh {
 }
 impl Drop for Mesh {
     fn drop(&mut self) {
+        // meshes that never got uploaded have nothing to delete(and maybe no gl context either)
+        if self.vao == 0 {
+            return;
+        }
         unsafe {
             gl::DeleteVertexArrays(1, &mut self.vao);
             gl::DeleteBuffers(1, &mut self.vbo);
@@ -200,6 +293,15 @@ impl Model {
             mesh.render();
         }
     }
+    /// weights: morph weights for each mesh id
+    pub fn apply_morph_weights(&mut self, weights: &HashMap<u32, Vec<f32>>) {
+        self.meshes.iter_mut().for_each(|mesh| {
+            if let Some(w) = weights.get(&mesh.mesh_id) {
+                mesh.set_morph_weights(w);
+            }
+        });
+    }
+
     pub fn recolor(&mut self, color: Vec3) {
         self.meshes.iter_mut().for_each(|mesh| {
             mesh.vertices.iter_mut().for_each(|vertex| {
@@ -239,3 +341,68 @@ pub fn add_tri(mesh: &mut Mesh, p1: Vertex, p2: Vertex, p3: Vertex) {
         bone_ids: p3.bone_ids,
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// two vertices with a target that lifts them and one that pushes them along x
+    fn mesh() -> Mesh {
+        let mut mesh = Mesh::default();
+        mesh.vertices = vec![Vertex::DEFAULT; 2];
+        mesh.morph_targets = vec![
+            MorphTarget {
+                positions: vec![vec3(0.0, 1.0, 0.0); 2],
+                normals: Vec::new(),
+            },
+            MorphTarget {
+                positions: vec![vec3(2.0, 0.0, 0.0); 2],
+                normals: Vec::new(),
+            },
+        ];
+        mesh.morph_weights = vec![0.0; 2];
+        mesh
+    }
+
+    #[test]
+    fn weights_scale_the_targets() {
+        let mut mesh = mesh();
+        mesh.set_morph_weights(&[0.5, 0.25]);
+
+        mesh.morphed_vertices().iter().for_each(|vertex| {
+            assert!((vertex.pos - vec3(0.5, 0.5, 0.0)).len() < 0.0001);
+        });
+    }
+
+    #[test]
+    fn targets_longer_than_the_mesh_are_cut_off() {
+        let mut mesh = mesh();
+        mesh.morph_targets[0].positions.push(vec3(0.0, 5.0, 0.0));
+        mesh.morph_targets[1].positions.truncate(1);
+        mesh.set_morph_weights(&[1.0, 1.0]);
+
+        let vertices = mesh.morphed_vertices();
+        assert_eq!(vertices.len(), 2);
+        assert!((vertices[0].pos - vec3(2.0, 1.0, 0.0)).len() < 0.0001);
+        assert!((vertices[1].pos - vec3(0.0, 1.0, 0.0)).len() < 0.0001);
+    }
+
+    #[test]
+    fn unchanged_weights_are_not_uploaded_again() {
+        let mut mesh = mesh();
+        assert!(mesh.set_morph_weights(&[1.0]));
+        assert_eq!(mesh.morph_weights, [1.0, 0.0]);
+
+        // the missing weight was already 0.0 and the extra one has no target
+        assert!(!mesh.set_morph_weights(&[1.0, 0.0, 3.0]));
+        assert!(!mesh.set_morph_weights(&[1.0]));
+        assert!(mesh.set_morph_weights(&[]));
+    }
+
+    #[test]
+    fn meshes_without_targets_never_change() {
+        let mut mesh = Mesh::default();
+        assert!(!mesh.set_morph_weights(&[1.0, 2.0]));
+        assert!(mesh.morph_weights.is_empty());
+    }
+}
@@ -1,12 +1,28 @@
+use crate::math::dual_quaternion::DualQuat;
 use crate::math::mat4::Mat4;
+use crate::math::quaternion::Quat;
 use crate::math::vec3::Vec3;
 
-use crate::src::animation::clip::Clip;
+use crate::src::animation::additive::AdditiveLayer;
+use crate::src::animation::blend_space::BlendSpace;
+use crate::src::animation::bone_mask::MaskedLayer;
+use crate::src::animation::clip::{AnimationEvent, Clip};
+use crate::src::animation::constraints::{Aim, LookAt};
+use crate::src::animation::crossfade::CrossFadeController;
+use crate::src::animation::foot_placement::FootPlacement;
+use crate::src::animation::ik::IKGoal;
 use crate::src::animation::pose::Pose;
+use crate::src::animation::root_motion::{self, RootMotion};
+use crate::src::animation::skinning::{self, SkinnedMesh, SkinningMode};
+use crate::src::animation::spring_bones::SpringChain;
+use crate::src::animation::state_machine::StateMachine;
 use crate::src::model::Model;
+use crate::src::physics::RayHit;
 use crate::src::skeleton::Skeleton;
 use crate::src::transform::Transform;
 
+use std::rc::Rc;
+
 // this could use some work
 // got to find a better way to impliment this and mesh struct.... and the model struct
 // this whole thing could use some work im just sort of doing my own stuff
@@ -15,11 +31,35 @@ pub struct Object {
     pub model: Model,
     pub transform: Transform,
     pub velocity: Vec3,
-    pub animations: Vec<Clip>,
+    /// shared clip library, clone the Rc's to give other objects the same clips
+    pub animations: Vec<Rc<Clip>>,
     pub skeleton: Skeleton,
     pub current_anim: usize,
     pub play_animation: bool,
     pub final_pose: Pose,
+    /// how long it takes to fade into a new animation after 'current_anim' changes
+    pub fade_time: f32,
+    pub fade_controller: CrossFadeController,
+    /// when set it drives the base pose instead of the cross fade controller(locomotion)
+    /// it only gives a pose, there is no root motion, no events and no morph weights while it plays
+    pub blend_space: Option<BlendSpace>,
+    /// clips overriding parts of the body, applied in order before the additive layers
+    pub masked_layers: Vec<MaskedLayer>,
+    /// applied in order on top of the faded pose
+    pub additive_layers: Vec<AdditiveLayer>,
+    /// when set the state machine picks 'current_anim' instead of gameplay code
+    pub state_machine: Option<StateMachine>,
+    /// solved in order after sampling so hands and feet reach their targets
+    pub ik_goals: Vec<IKGoal>,
+    pub foot_placement: Option<FootPlacement>,
+    /// head tracking, runs after the ik goals
+    pub look_at: Option<LookAt>,
+    /// applied in order after the look at
+    pub aims: Vec<Aim>,
+    /// secondary motion, update_spring_chains runs them after update_animation and place_feet
+    pub spring_chains: Vec<SpringChain>,
+    /// which palette gets sent to the shader, see get_pose and get_dual_quat_pose
+    pub skinning_mode: SkinningMode,
 }
 
 impl Object {
@@ -33,6 +73,18 @@ impl Object {
             play_animation: false,
             current_anim: 0,
             final_pose: Pose::new(),
+            fade_time: 0.3,
+            fade_controller: CrossFadeController::new(),
+            blend_space: None,
+            masked_layers: Vec::new(),
+            additive_layers: Vec::new(),
+            state_machine: None,
+            ik_goals: Vec::new(),
+            foot_placement: None,
+            look_at: None,
+            aims: Vec::new(),
+            spring_chains: Vec::new(),
+            skinning_mode: SkinningMode::Linear,
         }
     }
     pub fn change_pos(&mut self, n_pos: Vec3) -> &mut Self {
@@ -48,19 +100,120 @@ impl Object {
         self.model = model;
     }
 
+    /// events the playing clips passed during the last animation update as (clip, event)
+    pub fn get_animation_events(&self) -> &[(usize, AnimationEvent)] {
+        self.fade_controller.get_events()
+    }
+
+    /// move and turn the object by motion extracted from its clips
+    pub fn apply_root_motion(&mut self, motion: &RootMotion) {
+        let t = &mut self.transform;
+        t.translation = t.translation + t.orientation * (t.scaling * motion.translation);
+        t.orientation = (t.orientation * Quat::create(motion.yaw, root_motion::UP)).unit();
+    }
+
+    /// turn the head towards a world space point, does nothing without a look at constraint
+    pub fn look_at(&mut self, target: Vec3) {
+        if let Some(look_at) = self.look_at.as_mut() {
+            look_at.target = target;
+        }
+    }
+
+    /// bring a world space point into the space the skeleton is animated in
+    pub fn world_to_model(&self, point: Vec3) -> Vec3 {
+        self.transform.inverse().transform_point(&point)
+    }
+
     pub fn update_pos_with_velocity(&mut self) {
         self.transform.translation = self.transform.translation + self.velocity;
     }
 
-    pub fn update_animation(&mut self, time: f32) {
+    /// dt: time passed since the last update
+    pub fn update_animation(&mut self, dt: f32) {
         if self.play_animation {
-            self.final_pose = self.skeleton.rest_pose.clone();
-            // extract animation for each joint(bone)
-            self.animations[self.current_anim].sample(&mut self.final_pose, time);
+            let rest_pose = &self.skeleton.rest_pose;
+
+            if let Some(space) = self.blend_space.as_mut() {
+                space.update(rest_pose, dt);
+                self.final_pose = space.get_pose().clone();
+            } else {
+                if let Some(machine) = self.state_machine.as_mut() {
+                    if let Some((clip, duration)) = machine.update(&self.animations, dt) {
+                        self.fade_controller
+                            .fade_to(&self.animations, rest_pose, clip, duration);
+                    }
+                    self.current_anim = machine.get_current_clip();
+                }
+
+                // changing 'current_anim' fades into the new clip instead of snapping to it
+                self.fade_controller.fade_to(
+                    &self.animations,
+                    rest_pose,
+                    self.current_anim,
+                    self.fade_time,
+                );
+                // extract animation for each joint(bone)
+                self.fade_controller.update(rest_pose, dt);
+
+                self.final_pose = self.fade_controller.get_current_pose().clone();
+                self.apply_root_motion(&self.fade_controller.get_root_motion());
+                self.model
+                    .apply_morph_weights(self.fade_controller.get_weights());
+            }
+
+            for layer in self.masked_layers.iter_mut() {
+                layer.apply(&mut self.final_pose, dt);
+            }
+
+            for layer in self.additive_layers.iter_mut() {
+                layer.apply(&mut self.final_pose, dt);
+            }
+
+            for goal in self.ik_goals.iter() {
+                goal.solve(&mut self.final_pose, &self.transform);
+            }
+
+            if let Some(look_at) = &self.look_at {
+                look_at.apply(&mut self.final_pose, &self.transform);
+            }
+
+            for aim in self.aims.iter() {
+                aim.apply(&mut self.final_pose, &self.transform);
+            }
+        }
+    }
+
+    /// plant the feet on whatever the ray casts hit, call after update_animation
+    /// raycast: (origin, direction, max distance) in world space
+    pub fn place_feet<F>(&mut self, raycast: F)
+    where
+        F: Fn(Vec3, Vec3, f32) -> Option<RayHit>,
+    {
+        if !self.play_animation {
+            return;
+        }
+        if let Some(feet) = &self.foot_placement {
+            feet.apply(&mut self.final_pose, &self.transform, raycast);
+        }
+    }
+
+    /// the spring chains, call last(after place_feet) so they react to the finished pose
+    pub fn update_spring_chains(&mut self, dt: f32) {
+        if !self.play_animation {
+            return;
+        }
+        for chain in self.spring_chains.iter_mut() {
+            chain.update(&mut self.final_pose, &self.transform, dt);
         }
     }
 
     pub fn get_pose(&mut self) -> Vec<Mat4> {
+        self.get_skin_pose(None)
+    }
+
+    /// palette for the meshes bound with 'skin', see Mesh::skin
+    pub fn get_skin_pose(&mut self, skin: Option<usize>) -> Vec<Mat4> {
+        let inverse_bind_pose = self.skeleton.get_inverse_bind_pose(skin);
         let mut final_mats = Vec::new();
 
         let len = self.skeleton.rest_pose.joints.len();
@@ -72,7 +225,7 @@ impl Object {
             // only multiply with the joint that are actually sent to the shader
             // not sure if this is significant though
             for i in 0..len {
-                if let Some(inverse_pose) = self.skeleton.inverse_bind_pose[i] {
+                if let Some(inverse_pose) = inverse_bind_pose[i] {
                     // only get global transforms for joints being used
                     // to reduce iterations perfomed ofcourse
                     let world = self.final_pose.get_global_tranform(i);
@@ -86,7 +239,7 @@ impl Object {
         } else {
             for i in 0..len {
                 // only get global transforms for joints being used
-                if let Some(inverse_pose) = self.skeleton.inverse_bind_pose[i] {
+                if let Some(inverse_pose) = inverse_bind_pose[i] {
                     let world = self.skeleton.rest_pose.get_global_tranform(i);
 
                     final_mats[i] = world.to_mat() * inverse_pose;
@@ -99,4 +252,79 @@ impl Object {
 
         final_mats
     }
+
+    /// same palette as get_pose but as dual quaternions
+    /// dual quaternions can't hold scale, so None comes back when a joint of the palette is scaled
+    /// callers fall back to the linear palette then
+    pub fn get_dual_quat_pose(&mut self) -> Option<Vec<DualQuat>> {
+        self.get_dual_quat_skin_pose(None)
+    }
+
+    pub fn get_dual_quat_skin_pose(&mut self, skin: Option<usize>) -> Option<Vec<DualQuat>> {
+        // built from the finished matrices so scale in the pose and in the inverse bind pose can cancel out
+        let mats = self.get_skin_pose(skin);
+        if !mats.iter().all(skinning::is_rigid) {
+            return None;
+        }
+
+        Some(mats.iter().map(DualQuat::from_mat).collect())
+    }
+
+    pub fn set_skinning_mode(&mut self, mode: SkinningMode) -> &mut Self {
+        self.skinning_mode = mode;
+        self
+    }
+
+    /// every skin used by the model, meshes without one share None
+    pub fn get_used_skins(&self) -> Vec<Option<usize>> {
+        let mut skins = Vec::new();
+        self.model.meshes.iter().for_each(|mesh| {
+            if !skins.contains(&mesh.skin) {
+                skins.push(mesh.skin);
+            }
+        });
+
+        skins
+    }
+
+    /// the current pose applied to every mesh on the cpu, in model space
+    /// each mesh is skinned with the palette of its own skin
+    pub fn get_skinned_meshes(&mut self) -> Vec<SkinnedMesh> {
+        let mut result = Vec::with_capacity(self.model.meshes.len());
+
+        for skin in self.get_used_skins() {
+            match self.skinning_mode {
+                SkinningMode::Linear => {
+                    let palette = self.get_skin_pose(skin);
+                    self.model.meshes.iter().enumerate().for_each(|(i, mesh)| {
+                        if mesh.skin == skin {
+                            result.push((i, skinning::skin_mesh(mesh, &palette)));
+                        }
+                    });
+                }
+                SkinningMode::DualQuaternion => match self.get_dual_quat_skin_pose(skin) {
+                    Some(palette) => {
+                        self.model.meshes.iter().enumerate().for_each(|(i, mesh)| {
+                            if mesh.skin == skin {
+                                result.push((i, skinning::skin_mesh_dual_quat(mesh, &palette)));
+                            }
+                        });
+                    }
+                    // scaled joints, same fallback the shader gets
+                    None => {
+                        let palette = self.get_skin_pose(skin);
+                        self.model.meshes.iter().enumerate().for_each(|(i, mesh)| {
+                            if mesh.skin == skin {
+                                result.push((i, skinning::skin_mesh(mesh, &palette)));
+                            }
+                        });
+                    }
+                },
+            }
+        }
+
+        // back in mesh order
+        result.sort_by_key(|(i, _)| *i);
+        result.into_iter().map(|(_, mesh)| mesh).collect()
+    }
 }
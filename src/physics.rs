@@ -145,3 +145,260 @@ pub fn collision_aabb_aabb(aabb1: String, aabb2: String, shapes: &mut HashMap<St
         shapes.get_mut(&aabb1).unwrap().velocity = reflect(&new_velocity, &normal);
     }
 }
+
+//**** ray casting ****//
+// used for placing feet on the ground
+// shapes are treated the same way the collision functions treat them,
+// spheres use the average of their scaling as the radius and boxes use the scaling as half extents
+
+/// what a shape looks like to ray casts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Collider {
+    None,
+    Sphere,
+    AABB,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+/// dir is expected to be normalized
+pub fn ray_sphere(origin: Vec3, dir: Vec3, center: Vec3, radius: f32) -> Option<RayHit> {
+    let oc = origin - center;
+    let b = dot(&oc, &dir);
+    let c = dot(&oc, &oc) - radius * radius;
+    let discriminant = b * b - c;
+
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let root = discriminant.sqrt();
+    let mut distance = -b - root;
+    if distance < 0.0 {
+        // started inside the sphere
+        distance = -b + root;
+    }
+    if distance < 0.0 {
+        return None;
+    }
+
+    let point = origin + dir * distance;
+    Some(RayHit {
+        point,
+        normal: (point - center).unit(),
+        distance,
+    })
+}
+
+/// slab test, dir is expected to be normalized
+pub fn ray_aabb(origin: Vec3, dir: Vec3, center: Vec3, size: Vec3) -> Option<RayHit> {
+    let aabb = get_aabb(center, size);
+
+    let o = origin.to_array();
+    let d = dir.to_array();
+    let min = aabb.min.to_array();
+    let max = aabb.max.to_array();
+
+    let mut t_near = f32::NEG_INFINITY;
+    let mut t_far = f32::INFINITY;
+    let mut normal = Vec3::ZERO;
+
+    for i in 0..3 {
+        if d[i].abs() < 0.000001 {
+            // parallel to the slab, must already be inside it
+            if o[i] < min[i] || o[i] > max[i] {
+                return None;
+            }
+            continue;
+        }
+
+        let mut t1 = (min[i] - o[i]) / d[i];
+        let mut t2 = (max[i] - o[i]) / d[i];
+        let mut sign = -1.0;
+        if t1 > t2 {
+            std::mem::swap(&mut t1, &mut t2);
+            sign = 1.0;
+        }
+
+        if t1 > t_near {
+            t_near = t1;
+            let mut n = [0.0; 3];
+            n[i] = sign;
+            normal = Vec3::from(&n);
+        }
+        if t2 < t_far {
+            t_far = t2;
+        }
+        if t_near > t_far || t_far < 0.0 {
+            return None;
+        }
+    }
+
+    if t_near < 0.0 {
+        // started inside the box
+        return None;
+    }
+
+    Some(RayHit {
+        point: origin + dir * t_near,
+        normal,
+        distance: t_near,
+    })
+}
+
+/// closest hit against all shapes with a collider within max_distance
+pub fn raycast(
+    origin: Vec3,
+    dir: Vec3,
+    max_distance: f32,
+    shapes: &HashMap<String, Shape>,
+) -> Option<RayHit> {
+    let dir = dir.unit();
+    let mut closest: Option<RayHit> = None;
+
+    shapes.values().for_each(|shape| {
+        let pos = shape.transform.translation;
+        let size = shape.transform.scaling;
+
+        let hit = match shape.collider {
+            Collider::None => None,
+            Collider::Sphere => ray_sphere(origin, dir, pos, radius(&size)),
+            Collider::AABB => ray_aabb(origin, dir, pos, size),
+        };
+
+        if let Some(hit) = hit {
+            if hit.distance > max_distance {
+                return;
+            }
+            match closest {
+                Some(c) if c.distance <= hit.distance => {}
+                _ => closest = Some(hit),
+            }
+        }
+    });
+
+    closest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOWN: Vec3 = Vec3 {
+        x: 0.0,
+        y: -1.0,
+        z: 0.0,
+    };
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).len() < 0.0001
+    }
+
+    fn shape(pos: Vec3, size: Vec3, collider: Collider) -> Shape {
+        let mut shape = Shape::new();
+        shape
+            .reposition(pos)
+            .rescale(size)
+            .change_collider(collider);
+        shape
+    }
+
+    #[test]
+    fn ray_sphere_hits_the_near_side() {
+        let hit = ray_sphere(vec3(0.0, 10.0, 0.0), DOWN, vec3(0.0, 2.0, 0.0), 2.0).unwrap();
+
+        assert!(close(hit.point, vec3(0.0, 4.0, 0.0)));
+        assert!(close(hit.normal, vec3(0.0, 1.0, 0.0)));
+        assert!((hit.distance - 6.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn ray_sphere_misses() {
+        // passes beside it and points away from it
+        assert!(ray_sphere(vec3(3.0, 10.0, 0.0), DOWN, Vec3::ZERO, 2.0).is_none());
+        assert!(ray_sphere(vec3(0.0, 10.0, 0.0), -DOWN, Vec3::ZERO, 2.0).is_none());
+    }
+
+    #[test]
+    fn ray_sphere_from_inside_hits_the_far_side() {
+        let hit = ray_sphere(Vec3::ZERO, DOWN, Vec3::ZERO, 2.0).unwrap();
+
+        assert!(close(hit.point, vec3(0.0, -2.0, 0.0)));
+        assert!((hit.distance - 2.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn ray_aabb_hits_the_top() {
+        let hit = ray_aabb(
+            vec3(1.0, 10.0, -1.0),
+            DOWN,
+            vec3(0.0, -2.0, 0.0),
+            vec3(5.0, 2.0, 5.0),
+        )
+        .unwrap();
+
+        assert!(close(hit.point, vec3(1.0, 0.0, -1.0)));
+        assert!(close(hit.normal, vec3(0.0, 1.0, 0.0)));
+        assert!((hit.distance - 10.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn ray_aabb_hits_a_side() {
+        let dir = vec3(1.0, 0.0, 0.0);
+        let hit = ray_aabb(vec3(-10.0, 0.5, 0.0), dir, Vec3::ZERO, vec3(1.0, 1.0, 1.0)).unwrap();
+
+        assert!(close(hit.point, vec3(-1.0, 0.5, 0.0)));
+        assert!(close(hit.normal, vec3(-1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn ray_aabb_misses() {
+        let size = vec3(1.0, 1.0, 1.0);
+        assert!(ray_aabb(vec3(3.0, 10.0, 0.0), DOWN, Vec3::ZERO, size).is_none());
+        assert!(ray_aabb(vec3(0.0, 10.0, 0.0), -DOWN, Vec3::ZERO, size).is_none());
+    }
+
+    #[test]
+    fn ray_aabb_from_inside_is_ignored() {
+        let size = vec3(1.0, 1.0, 1.0);
+        assert!(ray_aabb(Vec3::ZERO, DOWN, Vec3::ZERO, size).is_none());
+    }
+
+    #[test]
+    fn raycast_returns_the_closest_hit_in_range() {
+        let mut shapes = HashMap::new();
+        shapes.insert(
+            String::from("floor"),
+            shape(vec3(0.0, -1.0, 0.0), vec3(10.0, 1.0, 10.0), Collider::AABB),
+        );
+        shapes.insert(
+            String::from("ball"),
+            shape(vec3(0.0, 3.0, 0.0), vec3(1.0, 1.0, 1.0), Collider::Sphere),
+        );
+
+        let hit = raycast(vec3(0.0, 10.0, 0.0), DOWN * 2.0, 100.0, &shapes).unwrap();
+        assert!(close(hit.point, vec3(0.0, 4.0, 0.0)));
+
+        // beside the ball only the floor is left, and only if the ray is long enough
+        let origin = vec3(5.0, 10.0, 0.0);
+        let hit = raycast(origin, DOWN, 100.0, &shapes).unwrap();
+        assert!(close(hit.point, vec3(5.0, 0.0, 0.0)));
+        assert!(raycast(origin, DOWN, 9.0, &shapes).is_none());
+    }
+
+    #[test]
+    fn shapes_without_a_collider_are_skipped() {
+        let mut shapes = HashMap::new();
+        shapes.insert(
+            String::from("torus"),
+            shape(Vec3::ZERO, vec3(5.0, 5.0, 5.0), Collider::None),
+        );
+
+        assert!(raycast(vec3(0.0, 10.0, 0.0), DOWN, 100.0, &shapes).is_none());
+    }
+}
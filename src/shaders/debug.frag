@@ -0,0 +1,8 @@
+#version 460
+
+in vec3 fragCol;
+out vec4 color;
+
+void main() {
+    color = vec4(fragCol, 1.0);
+}
@@ -1,5 +1,6 @@
 use crate::math::vec3::Vec3;
 use crate::src::model::Mesh;
+use crate::src::physics::Collider;
 use crate::src::shaders::Program;
 use crate::src::transform::Transform;
 
@@ -8,6 +9,8 @@ pub struct Shape {
     pub transform: Transform,
     pattern: Option<Pattern>,
     pub velocity: Vec3,
+    /// only used for ray casts at the moment
+    pub collider: Collider,
 }
 /// choose what pattern to give a shape
 
@@ -27,6 +30,7 @@ impl Shape {
             transform: Transform::DEFAULT,
             pattern: None,
             velocity: Vec3::ZERO,
+            collider: Collider::None,
         }
     }
 
@@ -45,6 +49,21 @@ impl Shape {
         self
     }
 
+    pub fn change_collider(&mut self, collider: Collider) -> &mut Self {
+        self.collider = collider;
+        self
+    }
+
+    /// darkness of the checkered or striped pattern, does nothing without one
+    pub fn set_pattern_shade(&mut self, shade: f32) -> &mut Self {
+        match self.pattern.as_mut() {
+            Some(Pattern::Checkered(a, _)) => *a = shade,
+            Some(Pattern::Striped(a, _, _)) => *a = shade,
+            None => {}
+        }
+        self
+    }
+
     pub fn reshape(&mut self, mesh: Mesh) -> &mut Self {
         self.mesh = mesh;
         self
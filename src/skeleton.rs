@@ -8,6 +8,17 @@ pub struct Skeleton {
     pub rest_pose: Pose,
     pub inverse_bind_pose: Vec<Option<Mat4>>,
     pub joint_names: Vec<String>,
+    /// files with several skinned meshes bind each one with its own inverse bind matrices
+    pub skins: Vec<Skin>,
+}
+
+/// the joints one mesh is bound to, joints and inverse bind pose are parallel
+#[derive(Clone)]
+pub struct Skin {
+    pub name: String,
+    /// indices into the rest pose
+    pub joints: Vec<usize>,
+    pub inverse_bind_pose: Vec<Mat4>,
 }
 
 impl Skeleton {
@@ -16,6 +27,27 @@ impl Skeleton {
             rest_pose: Pose::new(),
             inverse_bind_pose: Vec::new(),
             joint_names: Vec::new(),
+            skins: Vec::new(),
         }
     }
+
+    /// inverse bind pose laid out like the skeletons own but only holding the joints of 'skin'
+    /// None or a missing skin gives back the skeletons own
+    pub fn get_inverse_bind_pose(&self, skin: Option<usize>) -> Vec<Option<Mat4>> {
+        let Some(skin) = skin.and_then(|skin| self.skins.get(skin)) else {
+            return self.inverse_bind_pose.clone();
+        };
+
+        let mut result = vec![None; self.rest_pose.joints.len()];
+        skin.joints
+            .iter()
+            .zip(skin.inverse_bind_pose.iter())
+            .for_each(|(&joint, mat)| {
+                if joint < result.len() {
+                    result[joint] = Some(*mat);
+                }
+            });
+
+        result
+    }
 }
@@ -0,0 +1,354 @@
+use crate::gl;
+use crate::math::{mat4::*, vec2::*, vec3::*};
+use crate::src::animation::pose::Pose;
+use crate::src::object::Object;
+use crate::src::shaders::Program;
+use crate::src::transform::Transform;
+
+use std::mem::offset_of;
+use std::os::raw::c_void;
+
+// _______________________________________________________________________________________________________
+// _______________________________________________________________________________________________________
+// skeleton debug overlay
+// draws the joint hierarchy of an object as lines from every joint to its parent with a small cross on each joint
+// the rest pose can be drawn next to the animated pose, handy for spotting broken imports
+// there is no text rendering in the engine so labels come out as screen positions that get printed
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct LineVertex {
+    pos: Vec3,
+    col: Vec3,
+}
+
+/// joint name and where it ended up on screen, in pixels from the top left corner
+#[derive(Clone, Debug)]
+pub struct JointLabel {
+    pub name: String,
+    pub screen_pos: Vec2,
+}
+
+pub struct SkeletonDebug {
+    pub enabled: bool,
+    /// draw the rest pose next to the animated one
+    pub show_rest_pose: bool,
+    /// print the joint names with their screen positions on the next render, resets itself afterwards
+    pub print_labels: bool,
+    /// where the rest pose is drawn relative to the object, in world space
+    pub rest_offset: Vec3,
+    /// half the length of the joint markers, in world units
+    pub joint_size: f32,
+    pub bone_color: Vec3,
+    pub rest_color: Vec3,
+    pub joint_color: Vec3,
+
+    vertex_count: i32,
+    vao: u32,
+    vbo: u32,
+}
+
+impl SkeletonDebug {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            show_rest_pose: false,
+            print_labels: false,
+            rest_offset: vec3(15.0, 0.0, 0.0),
+            joint_size: 0.3,
+            bone_color: vec3(0.1, 1.0, 0.3),
+            rest_color: vec3(0.2, 0.5, 1.0),
+            joint_color: vec3(1.0, 0.9, 0.1),
+            vertex_count: 0,
+            vao: 0,
+            vbo: 0,
+        }
+    }
+
+    pub fn create(&mut self) {
+        unsafe {
+            gl::CreateVertexArrays(1, &mut self.vao);
+            gl::CreateBuffers(1, &mut self.vbo);
+
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+
+            let vert_size = std::mem::size_of::<LineVertex>();
+
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(
+                0,
+                3,
+                gl::FLOAT,
+                gl::FALSE,
+                vert_size as i32,
+                std::ptr::null(),
+            );
+
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(
+                1,
+                3,
+                gl::FLOAT,
+                gl::FALSE,
+                vert_size as i32,
+                offset_of!(LineVertex, col) as *const c_void,
+            );
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+        }
+    }
+
+    /// the pose currently shown on the object, rest pose if it isnt animating
+    fn animated_pose(object: &Object) -> &Pose {
+        if object.play_animation {
+            &object.final_pose
+        } else {
+            &object.skeleton.rest_pose
+        }
+    }
+
+    fn push_pose(
+        &self,
+        lines: &mut Vec<LineVertex>,
+        pose: &Pose,
+        model: &Transform,
+        offset: Vec3,
+        color: Vec3,
+    ) {
+        let positions: Vec<Vec3> = (0..pose.joints.len())
+            .map(|i| model.transform_point(&pose.get_global_tranform(i).translation) + offset)
+            .collect();
+
+        let s = self.joint_size;
+        positions.iter().enumerate().for_each(|(i, &pos)| {
+            let parent = pose.parents[i];
+            if parent >= 0 {
+                lines.push(LineVertex { pos, col: color });
+                lines.push(LineVertex {
+                    pos: positions[parent as usize],
+                    col: color,
+                });
+            }
+
+            for axis in [vec3(s, 0.0, 0.0), vec3(0.0, s, 0.0), vec3(0.0, 0.0, s)] {
+                lines.push(LineVertex {
+                    pos: pos - axis,
+                    col: self.joint_color,
+                });
+                lines.push(LineVertex {
+                    pos: pos + axis,
+                    col: self.joint_color,
+                });
+            }
+        });
+    }
+
+    /// world space lines for the object, pairs of vertices
+    fn build_lines(&self, object: &Object) -> Vec<LineVertex> {
+        let mut lines = Vec::new();
+
+        let pose = Self::animated_pose(object);
+        self.push_pose(
+            &mut lines,
+            pose,
+            &object.transform,
+            Vec3::ZERO,
+            self.bone_color,
+        );
+
+        if self.show_rest_pose {
+            self.push_pose(
+                &mut lines,
+                &object.skeleton.rest_pose,
+                &object.transform,
+                self.rest_offset,
+                self.rest_color,
+            );
+        }
+
+        lines
+    }
+
+    /// joint names projected onto the screen, joints behind the camera are left out
+    pub fn get_labels(
+        &self,
+        object: &Object,
+        view: Mat4,
+        projection: Mat4,
+        width: f32,
+        height: f32,
+    ) -> Vec<JointLabel> {
+        let view_proj = projection * view;
+        let pose = Self::animated_pose(object);
+
+        let mut labels = Vec::new();
+        for i in 0..pose.joints.len() {
+            let world = object
+                .transform
+                .transform_point(&pose.get_global_tranform(i).translation);
+            if let Some(screen_pos) = project(&view_proj, world, width, height) {
+                let name = object
+                    .skeleton
+                    .joint_names
+                    .get(i)
+                    .cloned()
+                    .unwrap_or(format!("joint {i}"));
+                labels.push(JointLabel { name, screen_pos });
+            }
+        }
+
+        labels
+    }
+
+    /// uses the "debug" shader, depth testing is turned off so the bones show through the mesh
+    pub fn render(&mut self, object: &Object, shader: &mut Program, view: Mat4, projection: Mat4) {
+        if !self.enabled {
+            return;
+        }
+        if self.vao == 0 {
+            self.create();
+        }
+
+        let lines = self.build_lines(object);
+        self.vertex_count = lines.len() as i32;
+
+        shader.set_use();
+        shader.update_mat4("view", view);
+        shader.update_mat4("projection", projection);
+
+        unsafe {
+            gl::NamedBufferData(
+                self.vbo,
+                (lines.len() * std::mem::size_of::<LineVertex>()) as isize,
+                lines.as_ptr() as *const c_void,
+                gl::DYNAMIC_DRAW,
+            );
+
+            gl::Disable(gl::DEPTH_TEST);
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::LINES, 0, self.vertex_count);
+            gl::BindVertexArray(0);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+}
+
+impl Drop for SkeletonDebug {
+    fn drop(&mut self) {
+        if self.vao == 0 {
+            return;
+        }
+        unsafe {
+            gl::DeleteVertexArrays(1, &mut self.vao);
+            gl::DeleteBuffers(1, &mut self.vbo);
+        }
+    }
+}
+
+/// world space point to pixels, None if it's behind the camera
+fn project(view_proj: &Mat4, p: Vec3, width: f32, height: f32) -> Option<Vec2> {
+    let d = &view_proj.data;
+    let clip = |r: usize| d[r][0] * p.x + d[r][1] * p.y + d[r][2] * p.z + d[r][3];
+
+    let w = clip(3);
+    if w <= 0.0 {
+        return None;
+    }
+    let ndc_x = clip(0) / w;
+    let ndc_y = clip(1) / w;
+
+    Some(Vec2 {
+        x: (ndc_x * 0.5 + 0.5) * width,
+        y: (1.0 - (ndc_y * 0.5 + 0.5)) * height,
+    })
+}
+
+/// dumps the joint hierarchy to stdout, one indented line per joint with its local and global position
+pub fn print_hierarchy(object: &Object) {
+    let pose = &object.skeleton.rest_pose;
+    let names = &object.skeleton.joint_names;
+
+    fn depth(pose: &Pose, mut joint: usize) -> usize {
+        let mut depth = 0;
+        while pose.parents[joint] >= 0 {
+            joint = pose.parents[joint] as usize;
+            depth += 1;
+        }
+        depth
+    }
+
+    for i in 0..pose.joints.len() {
+        let name = names.get(i).map(|n| n.as_str()).unwrap_or("unnamed");
+        let local = pose.joints[i].translation;
+        let global = pose.get_global_tranform(i).translation;
+        println!(
+            "{}{i} {name} parent {} local {:?} global {:?}",
+            "  ".repeat(depth(pose, i)),
+            pose.parents[i],
+            local.to_array(),
+            global.to_array(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a two joint arm standing at x = 10
+    fn object() -> Object {
+        let mut object = Object::new();
+        object.change_pos(vec3(10.0, 0.0, 0.0));
+        object.skeleton.joint_names = vec!["shoulder".into(), "hand".into()];
+        object.skeleton.rest_pose.resize(2);
+        object.skeleton.rest_pose.parents[1] = 0;
+        object.skeleton.rest_pose.joints[1].translation = vec3(0.0, 2.0, 0.0);
+        object
+    }
+
+    #[test]
+    fn lines_connect_joints_in_world_space() {
+        let object = object();
+        let mut debug = SkeletonDebug::new();
+
+        // one bone and a three line cross on each joint
+        let lines = debug.build_lines(&object);
+        assert_eq!(lines.len(), 2 + 2 * 6);
+        assert!((lines[6].pos - vec3(10.0, 2.0, 0.0)).len() < 0.0001);
+        assert!((lines[7].pos - vec3(10.0, 0.0, 0.0)).len() < 0.0001);
+
+        // the rest pose comes second, moved over by the offset
+        debug.show_rest_pose = true;
+        let lines = debug.build_lines(&object);
+        assert_eq!(lines.len(), 2 * (2 + 2 * 6));
+        assert!((lines[20].pos - vec3(25.0, 2.0, 0.0)).len() < 0.0001);
+        assert!(lines[20..]
+            .iter()
+            .take(2)
+            .all(|line| line.col == debug.rest_color));
+    }
+
+    #[test]
+    fn labels_are_left_out_behind_the_camera() {
+        let object = object();
+        let debug = SkeletonDebug::new();
+
+        // looking down -z from z = 5, the arm is in front at x = 10
+        let view = translate(&vec3(-10.0, 0.0, -5.0));
+        let projection = perspective(90.0, 1.0, 0.1, 100.0);
+        let labels = debug.get_labels(&object, view, projection, 100.0, 100.0);
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels[0].name, "shoulder");
+        assert!((labels[0].screen_pos.x - 50.0).abs() < 0.001);
+        assert!((labels[0].screen_pos.y - 50.0).abs() < 0.001);
+        // the hand is higher up so it's closer to the top of the screen
+        assert!(labels[1].screen_pos.y < 50.0);
+
+        let view = translate(&vec3(-10.0, 0.0, 5.0));
+        assert!(debug
+            .get_labels(&object, view, projection, 100.0, 100.0)
+            .is_empty());
+    }
+}
@@ -30,10 +30,17 @@ impl Transform {
     }
 
     pub fn lerp(&self, other: &Self, factor: f32) -> Transform {
+        // make sure both quaternions are in the same neighborhood
+        // or the blend will take the long way around
+        let mut orientation = other.orientation;
+        if Quat::dot(&self.orientation, &orientation) < 0.0 {
+            orientation = -orientation;
+        }
+
         Self {
             translation: self.translation.mix(other.translation, factor),
             scaling: self.scaling.mix(other.scaling, factor),
-            orientation: self.orientation.nlerp(other.orientation, factor),
+            orientation: self.orientation.nlerp(orientation, factor),
         }
     }
 
@@ -108,6 +115,11 @@ impl Transform {
         inv
     }
 
+    /// apply the transform to a point
+    pub fn transform_point(&self, point: &Vec3) -> Vec3 {
+        self.translation + self.orientation * (self.scaling * *point)
+    }
+
     pub fn combine(&self, rhs: &Self) -> Self {
         let mut out = Transform::DEFAULT;
 
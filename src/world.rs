@@ -1,18 +1,22 @@
 use super::animation::*;
+use super::animation::{clip::Clip, track_transform::TransformTrack};
 use super::assets::Assets;
 use super::camera::Camera;
 use super::foreign::*;
 use super::lights;
 use super::object::*;
-use super::physics;
+use super::physics::{self, Collider};
 use super::shaders;
 use super::shadows;
 use super::shapes::{cube::cube, shape::Pattern, shape::Shape, sphere::*, torus::torus};
+use super::skeleton::Skeleton;
+use super::skeleton_debug::{self, SkeletonDebug};
 use super::timer::Timer;
 use crate::math::{mat4::*, quaternion::Quat, vec3::*};
 
 use std::collections::HashMap;
 use std::path::Path;
+use std::rc::Rc;
 
 // abit messy but who cares
 // not sure why im bothering with comments as if anyone is going to read any of this
@@ -23,6 +27,11 @@ pub struct World {
     sun: lights::DirectionalLight,
     assets: Assets,
     shapes: HashMap<String, Shape>,
+    pub skeleton_debug: SkeletonDebug,
+    /// keyframed shapes, lights and camera
+    property_animations: Vec<property::PropertyAnimation>,
+    /// finished tweens get dropped, looping ones play until removed
+    tweens: Vec<tween::Tween>,
 }
 
 impl World {
@@ -51,34 +60,44 @@ impl World {
         assets.add_shader("shadow", s_shadow);
         assets.add_shader("animation", s_animation);
 
+        let s_debug = create_shader(
+            Path::new("shaders/debug.vert"),
+            Path::new("shaders/debug.frag"),
+        );
+        assets.add_shader("debug", s_debug);
+
         let mut shapes = HashMap::new();
         let mut shape = Shape::new();
         shape
             .reshape(sphere(200, 200, vec3(1.0, 1.0, 1.0)))
             .reposition(vec3(4.0, 30.0, 10.0))
             .rescale(vec3(4.0, 4.0, 4.0))
-            .change_pattern(Pattern::Checkered(0.3, 20));
+            .change_pattern(Pattern::Checkered(0.3, 20))
+            .change_collider(Collider::Sphere);
         shapes.insert(String::from("ball"), shape);
 
         let mut shape = Shape::new();
         shape
             .reshape(icosphere(4, vec3(1.0, 0.35, 0.06)))
             .reposition(vec3(15.0, 40.0, 10.0))
-            .rescale(vec3(7.0, 7.0, 7.0));
+            .rescale(vec3(7.0, 7.0, 7.0))
+            .change_collider(Collider::Sphere);
         shapes.insert(String::from("ball2"), shape);
 
         let mut shape = Shape::new();
         shape
             .reshape(cube(false, vec3(1.0, 0.13, 0.48)))
             .reposition(vec3(-15.0, 40.0, 20.0))
-            .rescale(vec3(6.0, 6.0, 6.0));
+            .rescale(vec3(6.0, 6.0, 6.0))
+            .change_collider(Collider::AABB);
         shapes.insert(String::from("cube"), shape);
 
         let mut shape = Shape::new();
         shape
             .reshape(cube(true, Vec3::ZERO))
             .reposition(vec3(5.0, 5.0, 5.0))
-            .rescale(vec3(5.0, 5.0, 5.0));
+            .rescale(vec3(5.0, 5.0, 5.0))
+            .change_collider(Collider::AABB);
         shapes.insert(String::from("cube2"), shape);
 
         let mut shape = Shape::new();
@@ -93,30 +112,59 @@ impl World {
             .reshape(cube(false, vec3(0.9, 0.9, 0.9)))
             .reposition(vec3(0.0, -2.0, 0.0))
             .rescale(vec3(1000.0, 2.0, 1000.0))
-            .change_pattern(Pattern::Striped(0.1, 0.005, 70));
+            .change_pattern(Pattern::Striped(0.1, 0.005, 70))
+            .change_collider(Collider::AABB);
         shapes.insert(String::from("platform"), shape);
 
         shapes.values_mut().for_each(|shape| {
             shape.create();
         });
 
+        // cube2 circles the scene while spinning, one full lap every 16 seconds
+        let mut track = TransformTrack::new();
+        track.position = basic::rotate_around_track(
+            vec3(0.0, 20.0, 20.0),
+            50.0,
+            22.5,
+            vec3(0.0, 1.0, 0.0),
+            16.0,
+            8.0,
+        );
+        track.rotation = basic::spin_track(90.0, vec3(1.0, 1.0, 0.0), 16.0, 8.0);
+        let mut clip = Clip::new();
+        clip.name = String::from("cube2 orbit");
+        clip.tracks.push(track);
+        clip.re_calculate_duration();
+
+        let mut orbit = property::PropertyAnimation::new(
+            property::AnimationTarget::Shape(String::from("cube2")),
+            Rc::new(clip),
+            0,
+        );
+        orbit.reference = shapes.get("cube2").unwrap().transform;
+        let property_animations = vec![orbit];
+
         assets.add_pointlight(lights::PointLight {
             pos: vec3(30.0, 20.0, -20.0),
             col: vec3(1.0, 1.0, 1.0),
+            intensity: 1.0,
         });
 
         assets.add_pointlight(lights::PointLight {
             pos: vec3(-30.0, 20.0, -20.0),
             col: vec3(1.0, 0.6, 0.01),
+            intensity: 1.0,
         });
 
         assets.add_pointlight(lights::PointLight {
             pos: vec3(30.0, 20.0, 40.0),
             col: vec3(1.0, 0.0, 1.0),
+            intensity: 1.0,
         });
         assets.add_pointlight(lights::PointLight {
             pos: vec3(-30.0, 20.0, 40.0),
             col: vec3(0.0, 1.0, 0.5),
+            intensity: 1.0,
         });
 
         let sun = lights::DirectionalLight {
@@ -131,8 +179,13 @@ impl World {
         player.model.meshes = file.extract_meshes();
         player.skeleton.rest_pose = file.extract_rest_pose();
         player.skeleton.inverse_bind_pose = file.extract_inverse_bind_mats();
+        player.skeleton.skins = file.extract_skins();
         player.skeleton.joint_names = file.extract_joint_names();
-        player.animations = file.extract_animations();
+        player.animations = file
+            .extract_animations()
+            .into_iter()
+            .map(|clip| Rc::new(clip.optimized(60.0)))
+            .collect();
         player
             .change_pos(vec3(0.0, 12.0, 3.0))
             .change_size(vec3(3.5, 3.5, 3.5));
@@ -142,7 +195,50 @@ impl World {
         player.play_animation = true;
         player.current_anim = 0;
 
-        let projection = perspective(45.0, ratio, 0.1, 1000.0);
+        // plant the feet on whatever is under them, distances are in world units so they follow the scale
+        player.foot_placement = foot_placement::FootPlacement::from_names(
+            &player.skeleton,
+            "Hips",
+            &[
+                ("LeftUpLeg", "LeftLeg", "LeftFoot"),
+                ("RightUpLeg", "RightLeg", "RightFoot"),
+            ],
+        )
+        .map(|mut feet| {
+            feet.max_step *= player.transform.scaling.y;
+            feet
+        });
+        if player.foot_placement.is_none() {
+            println!("no legs found for foot placement");
+        }
+
+        // upper chest, neck and head share the turn towards the camera
+        player.look_at = constraints::LookAt::from_chain(&player.skeleton, "Spine2", "Head");
+        if player.look_at.is_none() {
+            println!("no neck found for the look at");
+        }
+
+        // let the antennae wobble behind the head
+        player.spring_chains = spring_bones::SpringChain::find_all(&player.skeleton, "antenna");
+        player.spring_chains.iter_mut().for_each(|chain| {
+            chain.set_stiffness(80.0).set_damping(6.0);
+        });
+        if player.spring_chains.is_empty() {
+            println!("no antennae found for the spring chains");
+        }
+
+        // the orange light slowly breathes
+        let mut pulse = tween::Tween::ease(
+            1.0,
+            0.3,
+            1.5,
+            tween::Easing::QuadInOut,
+            tween::TweenTarget::LightIntensity(1),
+        );
+        pulse.set_mode(animator::PlayMode::PingPong);
+        let tweens = vec![pulse];
+
+        let projection = perspective(camera.fov, ratio, 0.1, 1000.0);
 
         Self {
             shapes,
@@ -151,31 +247,65 @@ impl World {
             player,
             assets,
             projection,
+            skeleton_debug: SkeletonDebug::new(),
+            property_animations,
+            tweens,
         }
     }
+    /// static scenery from the default scene of a gltf file, every node with a mesh becomes an object
+    pub fn load_scene(&mut self, path: &Path) {
+        let file = gltf::GltfFile::new(path);
+        match file.extract_default_scene() {
+            Some(scene) => self.assets.add_scene(scene),
+            None => println!("no scene found in {}", path.display()),
+        }
+    }
+    /// motion capture from a bvh file, retargeted onto the player by joint name and faded into
+    pub fn load_motion(&mut self, path: &Path) {
+        let file = match bvh::BvhFile::new(path) {
+            Ok(file) => file,
+            Err(e) => {
+                println!("{e}");
+                return;
+            }
+        };
+
+        let mut source = Skeleton::new();
+        source.rest_pose = file.extract_rest_pose();
+        source.joint_names = file.extract_joint_names();
+
+        let retargeter = retarget::Retargeter::new(&source, &self.player.skeleton);
+        let clip = retargeter.retarget_clip(&file.extract_clip());
+        self.player.animations.push(Rc::new(clip));
+        self.player.current_anim = self.player.animations.len() - 1;
+    }
+
     pub fn update_cam(&mut self, ratio: f32) -> &mut Self {
-        self.projection = perspective(45.0, ratio, 0.1, 1000.0);
+        self.projection = perspective(self.camera.fov, ratio, 0.1, 1000.0);
         self.camera.update_motion();
 
         self
     }
     pub fn update_animations(&mut self, timer: &Timer) -> &mut Self {
-        let center = vec3(0.0, 20.0, 20.0);
-        let axis = vec3(0.0, 1.0, 0.0);
-        let transform = &mut self.shapes.get_mut("cube2").unwrap().transform;
+        self.property_animations.iter_mut().for_each(|animation| {
+            animation.update(timer.delta);
+            animation.apply(&mut self.shapes, &mut self.assets.lights, &mut self.camera);
+        });
+        self.tweens.iter_mut().for_each(|tween| {
+            tween.update(timer.delta);
+            tween.apply(&mut self.shapes, &mut self.assets.lights, &mut self.camera);
+        });
+        self.tweens.retain(|tween| !tween.is_finished());
 
-        basic::spin(timer.elapsed, 90.0, vec3(1.0, 1.0, 0.0), transform);
+        // keep an eye on the camera
+        self.player.look_at(self.camera.pos);
+        self.player.update_animation(timer.delta);
 
-        basic::rotate_around(
-            center,
-            50.0,
-            22.5,
-            axis,
-            timer.elapsed,
-            &mut transform.translation,
-        );
-
-        self.player.update_animation(timer.elapsed);
+        let shapes = &self.shapes;
+        self.player.place_feet(|origin, dir, max_distance| {
+            physics::raycast(origin, dir, max_distance, shapes)
+        });
+        self.player.update_spring_chains(timer.delta);
 
         self
     }
@@ -246,6 +376,14 @@ impl World {
         shapes.values_mut().for_each(|shape| {
             shape.render(shader);
         });
+
+        // imported scenery, no patterns
+        shader.update_int("checkered", false as i32);
+        shader.update_int("subDivided", false as i32);
+        self.assets.objects.values_mut().for_each(|object| {
+            model_to_shader(object, shader);
+            object.model.render();
+        });
     }
 
     pub fn render_skeletal_animations(&mut self) {
@@ -271,13 +409,43 @@ impl World {
             pl_to_shader(lights[i], shader, i);
         }
 
-        let mats = &self.player.get_pose();
-        for i in 0..mats.len() {
-            shader.update_mat4(format!("boneMats[{i}]").as_str(), mats[i]);
+        model_to_shader(&mut self.player, shader);
+
+        // every skin gets its own palette, meshes are drawn right after their palette is sent
+        for skin in self.player.get_used_skins() {
+            palette_to_shader(&mut self.player, skin, shader);
+            self.player
+                .model
+                .meshes
+                .iter_mut()
+                .filter(|mesh| mesh.skin == skin)
+                .for_each(|mesh| mesh.render());
         }
+    }
 
-        model_to_shader(&mut self.player, shader);
-        self.player.model.render();
+    /// skeleton overlay on top of everything else, width and height are the window size for the labels
+    pub fn render_debug(&mut self, width: f32, height: f32) {
+        let shader = &mut self.assets.shaders.get_mut("debug").unwrap();
+        let view = self.camera.get_view();
+
+        self.skeleton_debug
+            .render(&self.player, shader, view, self.projection);
+
+        if self.skeleton_debug.print_labels {
+            self.skeleton_debug.print_labels = false;
+
+            println!();
+            skeleton_debug::print_hierarchy(&self.player);
+            self.skeleton_debug
+                .get_labels(&self.player, view, self.projection, width, height)
+                .iter()
+                .for_each(|label| {
+                    println!(
+                        "{} at ({:.0}, {:.0})",
+                        label.name, label.screen_pos.x, label.screen_pos.y
+                    );
+                });
+        }
     }
 }
 
@@ -287,6 +455,34 @@ fn model_to_shader(o: &mut Object, shader: &mut shaders::Program) {
     shader.update_int("textured", o.model.textured as i32);
 }
 
+// send the joint palette of one skin to the shader
+// a scaled palette can't be turned into dual quaternions so it goes as linear even in dual quaternion mode
+fn palette_to_shader(o: &mut Object, skin: Option<usize>, shader: &mut shaders::Program) {
+    let dqs = match o.skinning_mode {
+        skinning::SkinningMode::Linear => None,
+        skinning::SkinningMode::DualQuaternion => o.get_dual_quat_skin_pose(skin),
+    };
+
+    match dqs {
+        Some(dqs) => {
+            shader.update_int("skinningMode", 1);
+            // packed into the rows of boneMats, which end up as columns after the transpose
+            for (i, dq) in dqs.iter().enumerate() {
+                let packed = Mat4 {
+                    data: [dq.real.to_array(), dq.dual.to_array(), [0.0; 4], [0.0; 4]],
+                };
+                shader.update_mat4(format!("boneMats[{i}]").as_str(), packed);
+            }
+        }
+        None => {
+            shader.update_int("skinningMode", 0);
+            for (i, mat) in o.get_skin_pose(skin).iter().enumerate() {
+                shader.update_mat4(format!("boneMats[{i}]").as_str(), *mat);
+            }
+        }
+    }
+}
+
 use shaders::{Program, Shader};
 
 /// function assumes there will only be a vertex and fragment shader  
@@ -303,5 +499,5 @@ fn pl_to_shader(light: lights::PointLight, shader: &mut shaders::Program, i: usi
     let pos = format!("pointLights[{i}].position");
     let col = format!("pointLights[{i}].color");
     shader.update_vec3(pos.as_str(), light.pos);
-    shader.update_vec3(col.as_str(), light.col);
+    shader.update_vec3(col.as_str(), light.col * light.intensity);
 }